@@ -0,0 +1,160 @@
+use mongodb::bson::{Bson, Document};
+
+/// Rust keywords, strict and reserved, that can't be used as field names without the `r#` prefix.
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+    "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+    "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+    "where", "while", "yield",
+];
+
+/// Keywords that can't be raw identifiers either, so fields named after them get a `_` suffix.
+const PATH_KEYWORDS: &[&str] = &["crate", "self", "Self", "super"];
+
+/// Generates serde structs matching `document`, one per subdocument, starting with `name`.
+pub fn rust_struct(name: &str, document: &Document) -> String {
+    let mut structs = Vec::new();
+    let mut names = vec![String::from("Self")];
+    let name = unique_name(&mut names, "", pascal_case(name));
+    push_struct(&mut structs, &mut names, &name, document);
+    structs.join("\n")
+}
+
+/// Adds the struct `name` for `document` to `structs`, followed by those of its subdocuments.
+/// `names` holds the struct names taken so far, which the subdocuments' structs are kept apart
+/// from.
+fn push_struct(
+    structs: &mut Vec<String>,
+    names: &mut Vec<String>,
+    name: &str,
+    document: &Document,
+) {
+    let mut fields = Vec::new();
+    let mut nested = Vec::new();
+    for (key, value) in document {
+        let field = field_name(key);
+        let type_name = type_of(&mut nested, names, name, key, value);
+        if field.trim_start_matches("r#") != key {
+            fields.push(format!("    #[serde(rename = \"{key}\")]"));
+        }
+        fields.push(format!("    pub {field}: {type_name},"));
+    }
+
+    structs.push(format!(
+        "#[derive(Debug, Serialize, Deserialize)]\npub struct {name} {{\n{}\n}}\n",
+        fields.join("\n")
+    ));
+    for (name, document) in nested {
+        push_struct(structs, names, &name, document);
+    }
+}
+
+/// The Rust type for `value` under `key` in the struct `parent`, adding the subdocuments that need
+/// their own struct to `nested` under a name not in `names` yet.
+fn type_of<'a>(
+    nested: &mut Vec<(String, &'a Document)>,
+    names: &mut Vec<String>,
+    parent: &str,
+    key: &str,
+    value: &'a Bson,
+) -> String {
+    match value {
+        Bson::Double(_) => "f64".into(),
+        Bson::String(_) => "String".into(),
+        Bson::Boolean(_) => "bool".into(),
+        Bson::Int32(_) => "i32".into(),
+        Bson::Int64(_) => "i64".into(),
+        Bson::ObjectId(_) => "bson::oid::ObjectId".into(),
+        Bson::DateTime(_) => "bson::DateTime".into(),
+        Bson::Timestamp(_) => "bson::Timestamp".into(),
+        Bson::Decimal128(_) => "bson::Decimal128".into(),
+        Bson::Binary(_) => "bson::Binary".into(),
+        Bson::RegularExpression(_) => "bson::Regex".into(),
+        Bson::Null => "Option<bson::Bson>".into(),
+        Bson::Document(document) => {
+            let name = unique_name(names, parent, pascal_case(key));
+            nested.push((name.clone(), document));
+            name
+        }
+        Bson::Array(items) => match items.first() {
+            Some(item) => format!(
+                "Vec<{}>",
+                type_of(nested, names, parent, &singular(key), item)
+            ),
+            None => "Vec<bson::Bson>".into(),
+        },
+        _ => "bson::Bson".into(),
+    }
+}
+
+/// `_id` → `id`, `createdAt` → `created_at`, `first-name` → `first_name`.
+fn field_name(key: &str) -> String {
+    let mut name = String::new();
+    for c in key.trim_start_matches('_').chars() {
+        if c.is_uppercase() {
+            if !name.is_empty() && !name.ends_with('_') {
+                name.push('_');
+            }
+            name.extend(c.to_lowercase());
+        } else if c.is_alphanumeric() {
+            name.push(c);
+        } else if !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    if KEYWORDS.contains(&name.as_str()) {
+        name.insert_str(0, "r#");
+    } else if PATH_KEYWORDS.contains(&name.as_str()) {
+        name.push('_');
+    }
+    name
+}
+
+/// Takes `name` for a struct, or if another struct has it, `name` prefixed with `parent`, then
+/// numbered: `Address`, `UserAddress`, `UserAddress2`.
+fn unique_name(names: &mut Vec<String>, parent: &str, name: String) -> String {
+    let name = match names.contains(&name) {
+        true => format!("{parent}{name}"),
+        false => name,
+    };
+    let unique = match names.contains(&name) {
+        true => (2..)
+            .map(|n| format!("{name}{n}"))
+            .find(|numbered| !names.contains(numbered))
+            .unwrap(),
+        false => name,
+    };
+    names.push(unique.clone());
+    unique
+}
+
+/// `order_items` → `OrderItems`.
+fn pascal_case(key: &str) -> String {
+    let name: String = key
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+    match name.chars().next() {
+        Some(first) if first.is_alphabetic() => name,
+        _ => format!("Document{name}"),
+    }
+}
+
+/// Names the struct of array items after the item rather than the array, e.g. `tags` → `tag`.
+fn singular(key: &str) -> String {
+    match key.strip_suffix('s') {
+        Some(item) if !item.ends_with('s') => item.to_string(),
+        _ => key.to_string(),
+    }
+}
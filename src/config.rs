@@ -0,0 +1,65 @@
+use crate::render::Indent;
+use anyhow::{Context, Result};
+use mongodb::options::Collation;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, env, fs, io, path::PathBuf};
+
+/// Settings read from `$XDG_CONFIG_HOME/termongo/config.json`
+/// (`~/.config/termongo/config.json` by default). Command line flags take precedence.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub indent: Option<Indent>,
+    /// Parts of host names that mark production deployments, e.g. `prod`, in any case.
+    /// Connecting to one asks for confirmation and the footer says so throughout the session.
+    pub production_hosts: Vec<String>,
+    /// Pinned databases (`db`) and collections (`db.collection`), by host.
+    pub pins: HashMap<String, Vec<String>>,
+    /// Collations for querying collections, by host and then namespace.
+    pub collations: HashMap<String, HashMap<String, Collation>>,
+    /// Fields shown in the summary view of collections, by host and then namespace.
+    pub summaries: HashMap<String, HashMap<String, Vec<String>>>,
+    /// Columns left out of the table view of collections, by host and then namespace.
+    pub hidden_columns: HashMap<String, HashMap<String, Vec<String>>>,
+    /// Templates of URLs to open documents at, with `{id}` standing for their `_id`, by host and
+    /// then namespace.
+    pub urls: HashMap<String, HashMap<String, String>>,
+    /// `_id`s of bookmarked documents, as canonical extended JSON, by host and then namespace.
+    pub bookmarks: HashMap<String, HashMap<String, Vec<Value>>>,
+    /// Change stream resume tokens, as extended JSON, by host and then namespace.
+    pub resume_tokens: HashMap<String, HashMap<String, Value>>,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("termongo").join("config.json"))
+    }
+
+    /// Reads the config file, falling back to the defaults if there isn't one.
+    pub fn load() -> Result<Self> {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("invalid config file {}", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().context("no home directory to save the config file in")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
@@ -0,0 +1,504 @@
+use crate::{style::cformat, Args};
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use mongodb::{
+    bson::doc,
+    error::{Error, ErrorKind, WriteFailure},
+    event::command::{CommandEventHandler, CommandFailedEvent, CommandSucceededEvent},
+    options::{
+        Acknowledgment, AuthMechanism, ClientOptions, Credential, ReadConcern, ReadConcernLevel,
+        ReadPreference, ReadPreferenceOptions, SelectionCriteria, ServerAddress, ServerApiVersion,
+        TagSet, Tls, TlsOptions, WriteConcern,
+    },
+    Client,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How many times `ping` pings the server.
+pub const PINGS: usize = 5;
+
+/// Read preference modes, named like in connection strings.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ReadPreferenceMode {
+    #[value(name = "primary")]
+    Primary,
+    #[value(name = "primaryPreferred")]
+    PrimaryPreferred,
+    #[value(name = "secondary")]
+    Secondary,
+    #[value(name = "secondaryPreferred")]
+    SecondaryPreferred,
+    #[value(name = "nearest")]
+    Nearest,
+}
+
+/// Read concern levels, named like in connection strings.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ReadConcernMode {
+    #[value(name = "local")]
+    Local,
+    #[value(name = "available")]
+    Available,
+    #[value(name = "majority")]
+    Majority,
+    #[value(name = "linearizable")]
+    Linearizable,
+    #[value(name = "snapshot")]
+    Snapshot,
+}
+
+impl From<ReadConcernMode> for ReadConcern {
+    fn from(mode: ReadConcernMode) -> Self {
+        match mode {
+            ReadConcernMode::Local => ReadConcern::local(),
+            ReadConcernMode::Available => ReadConcern::available(),
+            ReadConcernMode::Majority => ReadConcern::majority(),
+            ReadConcernMode::Linearizable => ReadConcern::linearizable(),
+            ReadConcernMode::Snapshot => ReadConcern::snapshot(),
+        }
+    }
+}
+
+pub fn parse_acknowledgment(value: &str) -> Result<Acknowledgment, String> {
+    match value.parse::<u32>() {
+        Ok(nodes) => Ok(Acknowledgment::Nodes(nodes)),
+        Err(_) if value.is_empty() => Err(String::from("write concern can't be empty")),
+        Err(_) => Ok(Acknowledgment::from(value.to_string())),
+    }
+}
+
+/// Parses a tag set written as `key:value,key:value`.
+pub fn parse_tag_set(value: &str) -> Result<TagSet, String> {
+    value
+        .split(',')
+        .map(|tag| match tag.split_once(':') {
+            Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.trim().to_string()))
+            }
+            _ => Err(format!("expected `key:value`, got `{tag}`")),
+        })
+        .collect()
+}
+
+fn read_preference(mode: ReadPreferenceMode, tag_sets: &[TagSet]) -> Result<ReadPreference> {
+    let options = ReadPreferenceOptions::builder()
+        .tag_sets((!tag_sets.is_empty()).then(|| tag_sets.to_vec()))
+        .build();
+    Ok(match mode {
+        ReadPreferenceMode::Primary if !tag_sets.is_empty() => {
+            return Err(anyhow!("read preference tags can't be used with `primary`"))
+        }
+        ReadPreferenceMode::Primary => ReadPreference::Primary,
+        ReadPreferenceMode::PrimaryPreferred => ReadPreference::PrimaryPreferred { options },
+        ReadPreferenceMode::Secondary => ReadPreference::Secondary { options },
+        ReadPreferenceMode::SecondaryPreferred => ReadPreference::SecondaryPreferred { options },
+        ReadPreferenceMode::Nearest => ReadPreference::Nearest { options },
+    })
+}
+
+fn split(read_preference: &ReadPreference) -> (ReadPreferenceMode, Option<&ReadPreferenceOptions>) {
+    match read_preference {
+        ReadPreference::Primary => (ReadPreferenceMode::Primary, None),
+        ReadPreference::PrimaryPreferred { options } => {
+            (ReadPreferenceMode::PrimaryPreferred, Some(options))
+        }
+        ReadPreference::Secondary { options } => (ReadPreferenceMode::Secondary, Some(options)),
+        ReadPreference::SecondaryPreferred { options } => {
+            (ReadPreferenceMode::SecondaryPreferred, Some(options))
+        }
+        ReadPreference::Nearest { options } => (ReadPreferenceMode::Nearest, Some(options)),
+    }
+}
+
+pub fn describe_write_concern(write_concern: Option<&WriteConcern>) -> String {
+    let w = match write_concern.and_then(|wc| wc.w.as_ref()) {
+        Some(Acknowledgment::Nodes(nodes)) => nodes.to_string(),
+        Some(Acknowledgment::Majority) => String::from("majority"),
+        Some(Acknowledgment::Custom(tag)) => tag.clone(),
+        Some(_) | None => String::from("default"),
+    };
+    match write_concern.and_then(|wc| wc.journal) {
+        Some(journal) => format!("{w}, j: {journal}"),
+        None => w,
+    }
+}
+
+pub fn describe_read_preference(selection_criteria: Option<&SelectionCriteria>) -> String {
+    let read_preference = match selection_criteria {
+        Some(SelectionCriteria::ReadPreference(read_preference)) => read_preference,
+        Some(_) => return String::from("custom"),
+        None => return String::from("primary"),
+    };
+    let (mode, options) = split(read_preference);
+    let mode = mode
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default();
+    let tag_sets = options
+        .and_then(|options| options.tag_sets.as_ref())
+        .filter(|tag_sets| !tag_sets.is_empty());
+    match tag_sets {
+        Some(tag_sets) => {
+            let tag_sets: Vec<String> = tag_sets
+                .iter()
+                .map(|tags| {
+                    let mut tags: Vec<String> = tags
+                        .iter()
+                        .map(|(key, value)| format!("{key}:{value}"))
+                        .collect();
+                    tags.sort();
+                    tags.join(",")
+                })
+                .collect();
+            format!("{mode} (tags {})", tag_sets.join("; "))
+        }
+        None => mode,
+    }
+}
+
+pub fn describe_read_concern(read_concern: Option<&ReadConcern>) -> String {
+    match read_concern.map(|rc| &rc.level) {
+        Some(ReadConcernLevel::Local) => String::from("local"),
+        Some(ReadConcernLevel::Majority) => String::from("majority"),
+        Some(ReadConcernLevel::Linearizable) => String::from("linearizable"),
+        Some(ReadConcernLevel::Available) => String::from("available"),
+        Some(ReadConcernLevel::Snapshot) => String::from("snapshot"),
+        Some(ReadConcernLevel::Custom(level)) => level.clone(),
+        Some(_) | None => String::from("default"),
+    }
+}
+
+/// The options the driver took from the connection string and flags, besides the hosts, app
+/// name, write and read concerns and read preference the info shows on their own, named like in
+/// connection strings and leaving out the ones that weren't set.
+pub fn describe_options(options: &ClientOptions) -> Vec<(&'static str, String)> {
+    let millis = |duration: Option<Duration>| duration.map(|d| d.as_millis().to_string());
+    let credential = options.credential.as_ref();
+    let flag = |flag: Option<bool>| flag.map(|b| b.to_string());
+    let (tls, tls_options) = match &options.tls {
+        Some(Tls::Enabled(tls)) => (Some(String::from("true")), Some(tls)),
+        Some(Tls::Disabled) => (Some(String::from("false")), None),
+        None => (None, None),
+    };
+    let server_api = options.server_api.as_ref();
+    let compressors = options.compressors.as_ref().map(|compressors| {
+        let names: Vec<String> = compressors.iter().map(|c| format!("{c:?}")).collect();
+        names.join(", ")
+    });
+    let options = [
+        ("replicaSet", options.repl_set_name.clone()),
+        (
+            "directConnection",
+            options.direct_connection.map(|b| b.to_string()),
+        ),
+        ("loadBalanced", options.load_balanced.map(|b| b.to_string())),
+        ("tls", tls),
+        (
+            "tlsCAFile",
+            tls_options
+                .and_then(|tls| tls.ca_file_path.as_ref())
+                .map(|path| path.display().to_string()),
+        ),
+        (
+            "tlsCertificateKeyFile",
+            tls_options
+                .and_then(|tls| tls.cert_key_file_path.as_ref())
+                .map(|path| path.display().to_string()),
+        ),
+        (
+            "tlsAllowInvalidCertificates",
+            flag(tls_options.and_then(|tls| tls.allow_invalid_certificates)),
+        ),
+        (
+            "authMechanism",
+            credential
+                .and_then(|c| c.mechanism.as_ref())
+                .map(|m| m.as_str().to_string()),
+        ),
+        (
+            "authMechanismProperties",
+            credential
+                .and_then(|c| c.mechanism_properties.as_ref())
+                .map(|properties| properties.to_string()),
+        ),
+        ("authSource", credential.and_then(|c| c.source.clone())),
+        ("username", credential.and_then(|c| c.username.clone())),
+        ("defaultauthdb", options.default_database.clone()),
+        (
+            "readConcernLevel",
+            options
+                .read_concern
+                .as_ref()
+                .map(|rc| describe_read_concern(Some(rc))),
+        ),
+        (
+            "wtimeoutMS",
+            millis(options.write_concern.as_ref().and_then(|wc| wc.w_timeout)),
+        ),
+        ("retryWrites", options.retry_writes.map(|b| b.to_string())),
+        ("retryReads", options.retry_reads.map(|b| b.to_string())),
+        ("maxPoolSize", options.max_pool_size.map(|n| n.to_string())),
+        ("minPoolSize", options.min_pool_size.map(|n| n.to_string())),
+        (
+            "maxConnecting",
+            options.max_connecting.map(|n| n.to_string()),
+        ),
+        ("maxIdleTimeMS", millis(options.max_idle_time)),
+        ("connectTimeoutMS", millis(options.connect_timeout)),
+        (
+            "serverSelectionTimeoutMS",
+            millis(options.server_selection_timeout),
+        ),
+        ("heartbeatFrequencyMS", millis(options.heartbeat_freq)),
+        ("localThresholdMS", millis(options.local_threshold)),
+        ("srvMaxHosts", options.srv_max_hosts.map(|n| n.to_string())),
+        ("compressors", compressors),
+        (
+            "serverApi",
+            server_api.map(|api| match api.version {
+                ServerApiVersion::V1 => String::from("1"),
+                _ => format!("{:?}", api.version),
+            }),
+        ),
+        (
+            "serverApiStrict",
+            flag(server_api.and_then(|api| api.strict)),
+        ),
+        (
+            "serverApiDeprecationErrors",
+            flag(server_api.and_then(|api| api.deprecation_errors)),
+        ),
+    ];
+    options
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect()
+}
+
+/// Keeps track of the servers commands are sent to, to tell which mongos routers serve them.
+#[derive(Default)]
+pub struct Routers {
+    /// The server that answered the last command, and how many each one answered.
+    served: Mutex<(Option<String>, HashMap<String, u64>)>,
+}
+
+impl Routers {
+    fn record(&self, address: &ServerAddress) {
+        let address = address.to_string();
+        if let Ok(mut served) = self.served.lock() {
+            *served.1.entry(address.clone()).or_default() += 1;
+            served.0 = Some(address);
+        }
+    }
+
+    /// Renders how many commands each of `hosts` answered, marking the one that answered last.
+    pub fn describe(&self, hosts: &[ServerAddress]) -> Vec<String> {
+        let (last, counts) = match self.served.lock() {
+            Ok(served) => served.clone(),
+            Err(_) => return Vec::new(),
+        };
+        let hosts: Vec<String> = hosts.iter().map(ServerAddress::to_string).collect();
+        let width = hosts.iter().map(String::len).max().unwrap_or_default();
+        hosts
+            .iter()
+            .map(|host| {
+                let count = counts.get(host).copied().unwrap_or_default();
+                let last = match last.as_ref() == Some(host) {
+                    true => " (serving now)",
+                    false => "",
+                };
+                cformat!(
+                    "  <green>{:width$}</green>  {} commands{}",
+                    host,
+                    count,
+                    last,
+                    width = width
+                )
+            })
+            .collect()
+    }
+}
+
+impl CommandEventHandler for Routers {
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        self.record(&event.connection.address);
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        self.record(&event.connection.address);
+    }
+}
+
+/// Connects to `connection_string` with the options in `args`. The client only talks to the
+/// host `--mongos` pins, if it's given, but the options returned list them all.
+pub async fn connect(
+    connection_string: &str,
+    args: &Args,
+) -> Result<(Client, ClientOptions, Arc<Routers>)> {
+    let client_options = ClientOptions::parse(connection_string).await;
+    match client_options {
+        Ok(mut c) => {
+            if let Some(w) = &args.write_concern {
+                let write_concern = c.write_concern.get_or_insert_with(WriteConcern::default);
+                write_concern.w = Some(w.clone());
+            }
+            if let Some(level) = args.read_concern {
+                c.read_concern = Some(level.into());
+            }
+            if args.read_pref.is_some() || !args.read_pref_tags.is_empty() {
+                // Tags on their own apply to the mode from the connection string.
+                let mode = match (args.read_pref, &c.selection_criteria) {
+                    (Some(mode), _) => mode,
+                    (None, Some(SelectionCriteria::ReadPreference(current))) => split(current).0,
+                    (None, _) => ReadPreferenceMode::Primary,
+                };
+                let read_preference = read_preference(mode, &args.read_pref_tags)?;
+                c.selection_criteria = Some(SelectionCriteria::ReadPreference(read_preference));
+            }
+            if let Some(path) = &args.tls_cert_key_file {
+                use_certificate(&mut c, path)?;
+            }
+            match &args.app_name {
+                Some(name) => c.app_name = Some(name.clone()),
+                None => {
+                    c.app_name.get_or_insert_with(|| String::from("termongo"));
+                }
+            }
+            let routers = Arc::new(Routers::default());
+            let mut pinned = c.clone();
+            if let Some(mongos) = &args.mongos {
+                pinned.hosts = vec![pinned_host(&c.hosts, mongos)?];
+            }
+            pinned.command_event_handler = Some(routers.clone());
+            let client: Client = Client::with_options(pinned)?;
+            Ok((client, c, routers))
+        }
+        Err(e) => Err(anyhow!("Invalid connection string: {}", e)),
+    }
+}
+
+/// The one of `hosts` that `mongos` names, with or without its port.
+fn pinned_host(hosts: &[ServerAddress], mongos: &str) -> Result<ServerAddress> {
+    let mongos = mongos.to_lowercase();
+    hosts
+        .iter()
+        .find(|host| {
+            let host = host.to_string().to_lowercase();
+            host == mongos
+                || host
+                    .rsplit_once(':')
+                    .is_some_and(|(name, _)| name == mongos)
+        })
+        .cloned()
+        .ok_or_else(|| {
+            let hosts: Vec<String> = hosts.iter().map(ServerAddress::to_string).collect();
+            anyhow!(
+                "--mongos {mongos} isn't one of the hosts of the connection string: {}",
+                hosts.join(", ")
+            )
+        })
+}
+
+/// The first host of `options` that contains one of `patterns`, ignoring case.
+pub fn production_host(options: &ClientOptions, patterns: &[String]) -> Option<String> {
+    options
+        .hosts
+        .iter()
+        .map(|host| host.to_string())
+        .find(|host| {
+            let host = host.to_lowercase();
+            patterns
+                .iter()
+                .any(|pattern| !pattern.is_empty() && host.contains(&pattern.to_lowercase()))
+        })
+}
+
+/// Runs the `ping` command `PINGS` times, returning how long each round trip took.
+pub async fn ping(client: &Client) -> Result<Vec<Duration>> {
+    let admin = client.database("admin");
+    // The first command also opens the connection, so it isn't timed.
+    admin.run_command(doc! { "ping": 1 }, None).await?;
+    let mut round_trips = Vec::with_capacity(PINGS);
+    for _ in 0..PINGS {
+        let started = Instant::now();
+        admin.run_command(doc! { "ping": 1 }, None).await?;
+        round_trips.push(started.elapsed());
+    }
+    Ok(round_trips)
+}
+
+/// `min/avg/max` of the round trips, in milliseconds.
+pub fn describe_round_trips(round_trips: &[Duration]) -> String {
+    let millis: Vec<f64> = round_trips
+        .iter()
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .collect();
+    let min = millis.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = millis.iter().copied().fold(0.0, f64::max);
+    let avg = millis.iter().sum::<f64>() / millis.len().max(1) as f64;
+    format!("round trip min/avg/max: {min:.1}/{avg:.1}/{max:.1} ms")
+}
+
+/// Describes a failed write, explaining the error servers return for writes sent to a
+/// secondary, which happens with direct connections to one, and why a document failed
+/// validation.
+pub fn describe_write_error(error: &Error) -> String {
+    // NotWritablePrimary and NotPrimaryNoSecondaryOk.
+    const NOT_PRIMARY: [i32; 2] = [10107, 13435];
+    let code = match *error.kind {
+        ErrorKind::Command(ref command) => Some(command.code),
+        ErrorKind::Write(WriteFailure::WriteConcernError(ref concern)) => Some(concern.code),
+        ErrorKind::Write(WriteFailure::WriteError(ref write)) => Some(write.code),
+        _ => None,
+    };
+    match code {
+        Some(code) if NOT_PRIMARY.contains(&code) => String::from(
+            "cannot write: connected to a secondary \
+             (connect to the replica set or its primary rather than directly to a secondary)",
+        ),
+        // DocumentValidationFailure, whose details say which rule failed since MongoDB 5.0.
+        Some(121) => match *error.kind {
+            ErrorKind::Write(WriteFailure::WriteError(ref write)) if write.details.is_some() => {
+                format!(
+                    "document failed validation: {}",
+                    write.details.clone().unwrap_or_default()
+                )
+            }
+            _ => String::from("document failed validation"),
+        },
+        _ => error.to_string(),
+    }
+}
+
+/// Authenticates with the client certificate in the PEM file at `path`, which has to hold both
+/// the certificate and its private key.
+fn use_certificate(options: &mut ClientOptions, path: &Path) -> Result<()> {
+    // The driver only reads the file once it connects, so mistakes are caught here instead.
+    let pem = fs::read_to_string(path)
+        .with_context(|| format!("failed to read the certificate key file {}", path.display()))?;
+    if !pem.contains("-----BEGIN CERTIFICATE-----") {
+        return Err(anyhow!("no certificate in {}", path.display()));
+    }
+    if !pem.contains("PRIVATE KEY-----") {
+        return Err(anyhow!("no private key in {}", path.display()));
+    }
+
+    let mut tls = match options.tls.take() {
+        Some(Tls::Enabled(tls)) => tls,
+        _ => TlsOptions::default(),
+    };
+    tls.cert_key_file_path = Some(path.to_path_buf());
+    options.tls = Some(Tls::Enabled(tls));
+
+    let credential = options.credential.get_or_insert_with(Credential::default);
+    credential.mechanism = Some(AuthMechanism::MongoDbX509);
+    credential.source = Some(String::from("$external"));
+    credential.password = None;
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use crate::{render::Json, style::cformat};
+use mongodb::bson::{doc, Bson, Document};
+
+/// A field of either of two documents, with its value in each.
+struct Field<'a> {
+    path: String,
+    old: Option<&'a Bson>,
+    new: Option<&'a Bson>,
+}
+
+/// Lists the fields `new` adds, removes or changes compared to `old`, one line each,
+/// going into subdocuments so only the fields that changed are shown, followed by the
+/// (sub)documents whose fields `new` puts in another order.
+pub fn diff(old: &Document, new: &Document, json: Json) -> Vec<String> {
+    let mut fields = Vec::new();
+    push_fields(&mut fields, "", old, new, false);
+    let mut lines: Vec<String> = fields
+        .into_iter()
+        .map(|field| match (field.old, field.new) {
+            (Some(old), None) => cformat!("<red>- {}: {}</red>", field.path, show(old, json)),
+            (None, Some(new)) => cformat!("<green>+ {}: {}</green>", field.path, show(new, json)),
+            (Some(old), Some(new)) => {
+                let (old_text, new_text) = (show(old, json), show(new, json));
+                // Relaxed extended JSON looks the same for e.g. an int64 and an int32.
+                let types = match old_text == new_text {
+                    true => format!(" ({:?} → {:?})", old.element_type(), new.element_type()),
+                    false => String::new(),
+                };
+                cformat!(
+                    "<yellow>~ {}: {} → {}{}</yellow>",
+                    field.path,
+                    old_text,
+                    new_text,
+                    types
+                )
+            }
+            (None, None) => String::new(),
+        })
+        .collect();
+    push_reorders(&mut lines, "", old, new);
+    lines
+}
+
+/// Lays the fields of `left` and `right` out next to each other within `width` columns,
+/// highlighting the ones that differ: red when only on the left, green when only on the right
+/// and yellow when their values differ.
+pub fn side_by_side(left: &Document, right: &Document, json: Json, width: usize) -> Vec<String> {
+    let mut fields = Vec::new();
+    push_fields(&mut fields, "", left, right, true);
+    let path_width = fields
+        .iter()
+        .map(|field| field.path.chars().count())
+        .max()
+        .unwrap_or_default()
+        .min(width / 3);
+    let value_width = width.saturating_sub(path_width + 6) / 2;
+    let cut = |text: String, width: usize| -> String {
+        match text.chars().count() > width {
+            true => text
+                .chars()
+                .take(width.saturating_sub(1))
+                .chain(['…'])
+                .collect(),
+            false => text,
+        }
+    };
+
+    fields
+        .into_iter()
+        .map(|field| {
+            let path = cut(field.path, path_width);
+            let old = cut(
+                field.old.map(|v| show(v, json)).unwrap_or_default(),
+                value_width,
+            );
+            let new = cut(
+                field.new.map(|v| show(v, json)).unwrap_or_default(),
+                value_width,
+            );
+            let line = format!("{path:path_width$} │ {old:value_width$} │ {new}");
+            match (field.old, field.new) {
+                (Some(old), Some(new)) if old == new => line,
+                (Some(_), Some(_)) => cformat!("<yellow>{}</yellow>", line),
+                (Some(_), None) => cformat!("<red>{}</red>", line),
+                _ => cformat!("<green>{}</green>", line),
+            }
+        })
+        .collect()
+}
+
+/// Adds the fields of `old` and `new` that differ to `fields`, or all of them if `all` is set.
+/// Subdocuments in both are gone into rather than compared as a whole.
+fn push_fields<'a>(
+    fields: &mut Vec<Field<'a>>,
+    prefix: &str,
+    old: &'a Document,
+    new: &'a Document,
+    all: bool,
+) {
+    for (key, old_value) in old {
+        let path = format!("{prefix}{key}");
+        match (old_value, new.get(key)) {
+            (Bson::Document(old), Some(Bson::Document(new))) => {
+                push_fields(fields, &format!("{path}."), old, new, all)
+            }
+            (old_value, new_value) if all || new_value != Some(old_value) => fields.push(Field {
+                path,
+                old: Some(old_value),
+                new: new_value,
+            }),
+            _ => {}
+        }
+    }
+    for (key, new_value) in new {
+        if !old.contains_key(key) {
+            fields.push(Field {
+                path: format!("{prefix}{key}"),
+                old: None,
+                new: Some(new_value),
+            });
+        }
+    }
+}
+
+/// Adds a line for each document, going into subdocuments, whose fields `new` has in another
+/// order than `old`. Only the fields both have are compared.
+fn push_reorders(lines: &mut Vec<String>, prefix: &str, old: &Document, new: &Document) {
+    let common = |of: &Document, and: &Document| -> Vec<String> {
+        of.keys()
+            .filter(|key| and.contains_key(key))
+            .cloned()
+            .collect()
+    };
+    let (old_order, new_order) = (common(old, new), common(new, old));
+    if old_order != new_order {
+        let place = match prefix.strip_suffix('.') {
+            Some(path) => format!("fields of {path}"),
+            None => String::from("fields"),
+        };
+        lines.push(cformat!(
+            "<yellow>~ {} reordered: {} → {}</yellow>",
+            place,
+            old_order.join(", "),
+            new_order.join(", ")
+        ));
+    }
+    for (key, old_value) in old {
+        if let (Bson::Document(old), Some(Bson::Document(new))) = (old_value, new.get(key)) {
+            push_reorders(lines, &format!("{prefix}{key}."), old, new);
+        }
+    }
+}
+
+/// Gives the numbers of `new` that equal the ones in the same place in `old` the type they have
+/// there. Relaxed extended JSON writes an int64 that fits in an int32 like one, so it's read back
+/// as one even though it wasn't edited.
+pub fn keep_number_types(old: &Document, new: &mut Document) {
+    for (key, value) in new.iter_mut() {
+        if let Some(old) = old.get(key) {
+            keep_number_type(old, value);
+        }
+    }
+}
+
+fn keep_number_type(old: &Bson, new: &mut Bson) {
+    match (old, new) {
+        (Bson::Document(old), Bson::Document(new)) => keep_number_types(old, new),
+        (Bson::Array(old), Bson::Array(new)) => {
+            for (old, new) in old.iter().zip(new.iter_mut()) {
+                keep_number_type(old, new);
+            }
+        }
+        (Bson::Int64(old), new) if i32::try_from(*old).is_ok_and(|n| *new == Bson::Int32(n)) => {
+            *new = Bson::Int64(*old);
+        }
+        _ => {}
+    }
+}
+
+fn show(value: &Bson, json: Json) -> String {
+    json.to_value(&doc! { "value": value.clone() })["value"].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unedited_int64s_stay_int64s() {
+        let old =
+            doc! { "name": "a", "count": 5_i64, "sizes": [1_i64, 2_i64], "nested": { "n": 3_i64 } };
+        let mut new = doc! { "name": "b", "count": 5, "sizes": [1, 4], "nested": { "n": 3 } };
+        keep_number_types(&old, &mut new);
+        assert_eq!(
+            new,
+            doc! { "name": "b", "count": 5_i64, "sizes": [1_i64, 4], "nested": { "n": 3_i64 } }
+        );
+    }
+}
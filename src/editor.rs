@@ -0,0 +1,254 @@
+use crate::style::cformat;
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    terminal::{self, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{self, Write};
+
+/// Indentation inserted by tab and after an opening bracket.
+const INDENT: &str = "  ";
+
+/// Checks the text being edited, explaining what's wrong with it if it's invalid.
+pub type Validate<'a> = dyn Fn(&str) -> Result<(), String> + 'a;
+
+const PAIRS: [(char, char); 5] = [('{', '}'), ('[', ']'), ('(', ')'), ('"', '"'), ('\'', '\'')];
+
+/// Edits `text` in a full-screen, multi-line editor, checking it with `validate` after every
+/// key. Brackets and quotes are closed as they're opened. Returns the text once ctrl-s is
+/// pressed with valid input, or `None` if escape is. Expects the terminal to be in raw mode and
+/// leaves the screen as it found it.
+pub fn edit(title: &str, text: &str, validate: &Validate) -> Result<Option<String>> {
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut editor = Editor::new(text);
+    let result = editor.run(title, validate);
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+    result
+}
+
+struct Editor {
+    lines: Vec<Vec<char>>,
+    row: usize,
+    column: usize,
+    /// First line shown, when there are more than fit.
+    top: usize,
+}
+
+impl Editor {
+    fn new(text: &str) -> Self {
+        let mut lines: Vec<Vec<char>> = text.lines().map(|line| line.chars().collect()).collect();
+        if lines.is_empty() {
+            lines.push(Vec::new());
+        }
+        let row = lines.len() - 1;
+        let column = lines[row].len();
+        Editor {
+            lines,
+            row,
+            column,
+            top: 0,
+        }
+    }
+
+    fn text(&self) -> String {
+        let lines: Vec<String> = self
+            .lines
+            .iter()
+            .map(|line| line.iter().collect())
+            .collect();
+        lines.join("\n")
+    }
+
+    fn run(&mut self, title: &str, validate: &Validate) -> Result<Option<String>> {
+        loop {
+            let validity = validate(&self.text());
+            self.draw(title, &validity)?;
+            let event = match event::read()? {
+                Event::Key(event) => event,
+                _ => continue,
+            };
+            let control = event.modifiers.contains(KeyModifiers::CONTROL);
+            match event.code {
+                KeyCode::Char('s') if control && validity.is_ok() => return Ok(Some(self.text())),
+                KeyCode::Char('c') if control => return Ok(None),
+                KeyCode::Char(_) if control => {}
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char(c) => self.type_char(c),
+                KeyCode::Tab => INDENT.chars().for_each(|c| self.insert(c)),
+                KeyCode::Enter => self.new_line(),
+                KeyCode::Backspace => self.backspace(),
+                KeyCode::Delete => self.delete(),
+                KeyCode::Left if self.column > 0 => self.column -= 1,
+                KeyCode::Left if self.row > 0 => {
+                    self.row -= 1;
+                    self.column = self.lines[self.row].len();
+                }
+                KeyCode::Right if self.column < self.lines[self.row].len() => self.column += 1,
+                KeyCode::Right if self.row + 1 < self.lines.len() => {
+                    self.row += 1;
+                    self.column = 0;
+                }
+                KeyCode::Up if self.row > 0 => {
+                    self.row -= 1;
+                    self.column = self.column.min(self.lines[self.row].len());
+                }
+                KeyCode::Down if self.row + 1 < self.lines.len() => {
+                    self.row += 1;
+                    self.column = self.column.min(self.lines[self.row].len());
+                }
+                KeyCode::Home => self.column = 0,
+                KeyCode::End => self.column = self.lines[self.row].len(),
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&mut self, title: &str, validity: &Result<(), String>) -> Result<()> {
+        let mut stdout = io::stdout();
+        let (columns, rows) = terminal::size()?;
+        // The borders, and the message and keys below them, take four rows.
+        let height = (rows as usize).saturating_sub(4).max(1);
+        if self.row < self.top {
+            self.top = self.row;
+        } else if self.row >= self.top + height {
+            self.top = self.row + 1 - height;
+        }
+
+        let width = (columns as usize).saturating_sub(2);
+        let border = |left: &str, label: &str, right: &str| {
+            let fill = width.saturating_sub(label.chars().count());
+            let line = format!("{left}{label}{}{right}", "─".repeat(fill));
+            paint(&line, validity)
+        };
+        let side = paint("│", validity);
+        execute!(
+            stdout,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All)
+        )?;
+        print!("{}\r\n", border("┌", &format!(" {title} "), "┐"));
+        for line in self.lines.iter().skip(self.top).take(height) {
+            let line: String = line.iter().take(width.saturating_sub(1)).collect();
+            print!("{side} {line}\r\n");
+        }
+        for _ in self.lines.len().saturating_sub(self.top)..height {
+            print!("{side}\r\n");
+        }
+        print!("{}\r\n", border("└", "", "┘"));
+        match validity {
+            Ok(()) => print!("{}\r\n", cformat!("<green>valid</green>")),
+            Err(e) => print!("{}\r\n", cformat!("<red>{}</red>", e)),
+        }
+        print!("ctrl-s: submit | esc: cancel");
+        execute!(
+            stdout,
+            cursor::MoveTo((self.column + 2) as u16, (self.row - self.top + 1) as u16)
+        )?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn insert(&mut self, c: char) {
+        self.lines[self.row].insert(self.column, c);
+        self.column += 1;
+    }
+
+    fn next_char(&self) -> Option<char> {
+        self.lines[self.row].get(self.column).copied()
+    }
+
+    fn previous_char(&self) -> Option<char> {
+        self.column
+            .checked_sub(1)
+            .and_then(|i| self.lines[self.row].get(i).copied())
+    }
+
+    /// Inserts `c`, closing brackets and quotes it opens and typing over the closing ones
+    /// already there.
+    fn type_char(&mut self, c: char) {
+        if self.next_char() == Some(c) && PAIRS.iter().any(|&(_, close)| close == c) {
+            self.column += 1;
+            return;
+        }
+        self.insert(c);
+        if let Some(&(_, close)) = PAIRS.iter().find(|&&(open, _)| open == c) {
+            // Quotes in a word, e.g. an apostrophe, aren't opening ones.
+            let opens = c != close
+                || self.column < 2
+                || !self.lines[self.row][self.column - 2].is_alphanumeric();
+            if opens {
+                self.lines[self.row].insert(self.column, close);
+            }
+        }
+    }
+
+    /// Splits the line at the cursor, keeping its indentation. Between brackets, the closing
+    /// one goes on a line of its own and the cursor on an indented one in between.
+    fn new_line(&mut self) {
+        let indent: Vec<char> = self.lines[self.row]
+            .iter()
+            .take_while(|c| c.is_whitespace())
+            .copied()
+            .collect();
+        let between = matches!(
+            (self.previous_char(), self.next_char()),
+            (Some('{'), Some('}')) | (Some('['), Some(']'))
+        );
+        let rest = self.lines[self.row].split_off(self.column);
+        let rest = rest.into_iter().skip_while(|c| c.is_whitespace());
+        let mut inner = indent.clone();
+        if between {
+            inner.extend(INDENT.chars());
+            let mut closing = indent;
+            closing.extend(rest);
+            self.lines.insert(self.row + 1, closing);
+            self.column = inner.len();
+        } else {
+            self.column = inner.len();
+            inner.extend(rest);
+        }
+        self.row += 1;
+        self.lines.insert(self.row, inner);
+    }
+
+    /// Deletes the character before the cursor, along with its closing counterpart if the
+    /// pair is empty, or joins the line with the previous one.
+    fn backspace(&mut self) {
+        if self.column == 0 {
+            if self.row > 0 {
+                let line = self.lines.remove(self.row);
+                self.row -= 1;
+                self.column = self.lines[self.row].len();
+                self.lines[self.row].extend(line);
+            }
+            return;
+        }
+        let empty_pair = PAIRS
+            .iter()
+            .any(|&pair| (self.previous_char(), self.next_char()) == (Some(pair.0), Some(pair.1)));
+        self.column -= 1;
+        self.lines[self.row].remove(self.column);
+        if empty_pair {
+            self.lines[self.row].remove(self.column);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.column < self.lines[self.row].len() {
+            self.lines[self.row].remove(self.column);
+        } else if self.row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].extend(line);
+        }
+    }
+}
+
+/// Colours `text` green if the input is valid and red otherwise.
+fn paint(text: &str, validity: &Result<(), String>) -> String {
+    match validity {
+        Ok(()) => cformat!("<green>{}</green>", text),
+        Err(_) => cformat!("<red>{}</red>", text),
+    }
+}
@@ -0,0 +1,16 @@
+use anyhow::anyhow;
+use mongodb::error::{Error, ErrorKind};
+
+/// Whether the server refused a command because the user lacks the privileges for it.
+pub fn is_unauthorized(error: &Error) -> bool {
+    matches!(*error.kind, ErrorKind::Command(ref command) if command.code == 13)
+}
+
+/// Explains that the user isn't permitted to `what`, e.g. `read the oplog`, if that's why
+/// `error` happened.
+pub fn describe_unauthorized(error: Error, what: &str) -> anyhow::Error {
+    match is_unauthorized(&error) {
+        true => anyhow!("not permitted to {what}"),
+        false => error.into(),
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{query, render::Json};
+use anyhow::{anyhow, Result};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::Document,
+    options::{ClientOptions, FindOneOptions, FindOptions},
+    Client, Database,
+};
+use serde_json::Value;
+
+/// A query in the subset of shell syntax `--eval` supports.
+struct Query {
+    collection: String,
+    method: Method,
+}
+
+enum Method {
+    Find {
+        filter: Option<Document>,
+        projection: Option<Document>,
+        sort: Option<Document>,
+        skip: Option<u64>,
+        limit: Option<i64>,
+    },
+    FindOne {
+        filter: Option<Document>,
+        projection: Option<Document>,
+    },
+    Count {
+        filter: Option<Document>,
+    },
+}
+
+/// Runs each of `expressions` against the database of the connection string,
+/// printing the results as relaxed extended JSON.
+pub async fn run(client: &Client, options: &ClientOptions, expressions: &[String]) -> Result<()> {
+    let database = options.default_database.as_deref().ok_or_else(|| {
+        anyhow!("--eval needs a database in the connection string, e.g. mongodb://host/db")
+    })?;
+    let database = client.database(database);
+
+    for expression in expressions {
+        let output = evaluate(&database, expression).await?;
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    }
+    Ok(())
+}
+
+/// Runs `expression` against `database`, returning the results as relaxed extended JSON.
+pub async fn evaluate(database: &Database, expression: &str) -> Result<Value> {
+    let query = parse(expression).map_err(|e| anyhow!("{expression}: {e}"))?;
+    let collection = database.collection::<Document>(&query.collection);
+    let output = match query.method {
+        Method::Find {
+            filter,
+            projection,
+            sort,
+            skip,
+            limit,
+        } => {
+            let options = FindOptions::builder()
+                .projection(projection)
+                .sort(sort)
+                .skip(skip)
+                .limit(limit)
+                .build();
+            let documents: Vec<Document> = collection
+                .find(filter, options)
+                .await?
+                .try_collect()
+                .await?;
+            Value::Array(
+                documents
+                    .iter()
+                    .map(|document| Json::Relaxed.to_value(document))
+                    .collect(),
+            )
+        }
+        Method::FindOne { filter, projection } => {
+            let options = FindOneOptions::builder().projection(projection).build();
+            match collection.find_one(filter, options).await? {
+                Some(document) => Json::Relaxed.to_value(&document),
+                None => Value::Null,
+            }
+        }
+        Method::Count { filter } => Value::from(collection.count_documents(filter, None).await?),
+    };
+    Ok(output)
+}
+
+/// Parses `db.<collection>.find(…)` with optional `.sort(…)`, `.skip(n)` and `.limit(n)`,
+/// `db.<collection>.findOne(…)` and `db.<collection>.count(…)`.
+fn parse(expression: &str) -> Result<Query, String> {
+    let expression = expression.trim().trim_end_matches(';');
+    let rest = expression
+        .strip_prefix("db.")
+        .ok_or("expected db.<collection>.<method>(…)")?;
+    // Collection names may contain dots, so the name ends at the first `.method(`.
+    let (collection, name, call) = rest
+        .match_indices('.')
+        .find_map(|(i, _)| method_call(&rest[i + 1..]).map(|(name, call)| (&rest[..i], name, call)))
+        .ok_or("expected db.<collection>.<method>(…)")?;
+    let (arguments, mut rest) = split_arguments(call)?;
+
+    let mut method = match name {
+        "find" => {
+            let (filter, projection) = filter_and_projection(&arguments)?;
+            Method::Find {
+                filter,
+                projection,
+                sort: None,
+                skip: None,
+                limit: None,
+            }
+        }
+        "findOne" => {
+            let (filter, projection) = filter_and_projection(&arguments)?;
+            Method::FindOne { filter, projection }
+        }
+        "count" | "countDocuments" => match arguments.as_slice() {
+            [] => Method::Count { filter: None },
+            [filter] => Method::Count {
+                filter: Some(query::parse_document(filter)?),
+            },
+            _ => return Err(format!("{name} takes at most a filter")),
+        },
+        _ => {
+            return Err(format!(
+                "unsupported method {name}, expected find, findOne or count"
+            ))
+        }
+    };
+
+    while !rest.trim().is_empty() {
+        let (name, call) = rest
+            .trim()
+            .strip_prefix('.')
+            .and_then(method_call)
+            .ok_or_else(|| format!("unexpected `{}`", rest.trim()))?;
+        let (arguments, next) = split_arguments(call)?;
+        rest = next;
+
+        let (sort, skip, limit) = match &mut method {
+            Method::Find {
+                sort, skip, limit, ..
+            } => (sort, skip, limit),
+            _ => return Err(format!("{name}() can only follow find()")),
+        };
+        let argument = match arguments.as_slice() {
+            [argument] => *argument,
+            _ => return Err(format!("{name} takes one argument")),
+        };
+        match name {
+            "sort" => *sort = Some(query::parse_document(argument)?),
+            "skip" => *skip = Some(argument.parse().map_err(|_| "skip takes a number")?),
+            "limit" => *limit = Some(argument.parse().map_err(|_| "limit takes a number")?),
+            _ => {
+                return Err(format!(
+                    "unsupported cursor method {name}, expected sort, skip or limit"
+                ))
+            }
+        }
+    }
+
+    Ok(Query {
+        collection: collection.to_string(),
+        method,
+    })
+}
+
+fn filter_and_projection(
+    arguments: &[&str],
+) -> Result<(Option<Document>, Option<Document>), String> {
+    if arguments.len() > 2 {
+        return Err(String::from("expected at most a filter and a projection"));
+    }
+    let filter = arguments
+        .first()
+        .map(|a| query::parse_document(a))
+        .transpose()?;
+    let projection = arguments
+        .get(1)
+        .map(|a| query::parse_document(a))
+        .transpose()?;
+    Ok((filter, projection))
+}
+
+/// Splits `name(…)…` into the name and the call starting at the opening parenthesis.
+fn method_call(input: &str) -> Option<(&str, &str)> {
+    let end = input
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|&end| end > 0)?;
+    input[end..]
+        .starts_with('(')
+        .then(|| (&input[..end], &input[end..]))
+}
+
+/// Splits the arguments of the call `input` starts with on top-level commas,
+/// returning them along with what follows the closing parenthesis.
+fn split_arguments(input: &str) -> Result<(Vec<&str>, &str), String> {
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 1;
+    for (i, c) in input.char_indices() {
+        if let Some(open) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == open {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let last = input[start..i].trim();
+                    if !last.is_empty() || !arguments.is_empty() {
+                        arguments.push(last);
+                    }
+                    return Ok((arguments, &input[i + 1..]));
+                }
+            }
+            ',' if depth == 1 => {
+                arguments.push(input[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    Err(String::from("unbalanced parentheses"))
+}
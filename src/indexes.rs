@@ -0,0 +1,186 @@
+use crate::{
+    connection::describe_write_error,
+    query,
+    style::{cformat, cprintln},
+    ui,
+};
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{self, ClearType},
+};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::Document,
+    error::{ErrorKind, WriteFailure},
+    options::IndexOptions,
+    Collection, IndexModel,
+};
+use std::{io, time::Duration};
+
+/// Lists the indexes of `collection` until q or escape is pressed.
+/// Creating and dropping indexes is refused when `read_only` is set, and confirming a drop is
+/// cancelled after `confirm_timeout`.
+pub async fn browse(
+    collection: &Collection<Document>,
+    read_only: bool,
+    confirm_timeout: Option<Duration>,
+) -> Result<()> {
+    let mut indexes = list(collection).await?;
+    draw(collection, &indexes)?;
+
+    loop {
+        if let Event::Key(event) = event::read()? {
+            match event.code {
+                _ if ui::is_interrupt(&event) => return Ok(()),
+                KeyCode::Char('j') => execute!(io::stdout(), cursor::MoveDown(1))?,
+                KeyCode::Char('k') => execute!(io::stdout(), cursor::MoveUp(1))?,
+                KeyCode::Char('c' | 'd') if read_only => ui::status("read-only mode")?,
+                KeyCode::Char('c') => {
+                    if let Some(message) = create(collection).await? {
+                        indexes = list(collection).await?;
+                        draw(collection, &indexes)?;
+                        ui::status(&message)?;
+                    }
+                }
+                KeyCode::Char('d') => {
+                    let index = (cursor::position()?.1 as usize).checked_sub(1);
+                    let name = index
+                        .and_then(|i| indexes.get(i))
+                        .and_then(|index| index.options.as_ref())
+                        .and_then(|options| options.name.clone());
+                    if let Some(message) = drop(collection, name, confirm_timeout).await? {
+                        indexes = list(collection).await?;
+                        draw(collection, &indexes)?;
+                        ui::status(&message)?;
+                    }
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The indexes of `collection`.
+pub async fn list(collection: &Collection<Document>) -> Result<Vec<IndexModel>> {
+    Ok(collection.list_indexes(None).await?.try_collect().await?)
+}
+
+fn draw(collection: &Collection<Document>, indexes: &[IndexModel]) -> Result<()> {
+    terminal::disable_raw_mode()?;
+    print!(
+        "{}{}",
+        cursor::MoveTo(0, 0),
+        terminal::Clear(ClearType::All),
+    );
+    cprintln!(
+        "<yellow>{}</yellow> <cyan>indexes (c: create, d: drop, q: back)</cyan>",
+        collection.namespace()
+    );
+    for index in indexes {
+        let options = index.options.as_ref();
+        let name = options.and_then(|o| o.name.as_deref()).unwrap_or("?");
+        let mut flags = String::new();
+        if options.and_then(|o| o.unique) == Some(true) {
+            flags.push_str(" unique");
+        }
+        if options.and_then(|o| o.sparse) == Some(true) {
+            flags.push_str(" sparse");
+        }
+        if let Some(ttl) = options.and_then(|o| o.expire_after) {
+            flags.push_str(&format!(" ttl {}s", ttl.as_secs()));
+        }
+        println!(
+            "{} {}{}",
+            cformat!("<green>{}</green>", name),
+            index.keys,
+            cformat!("<magenta>{}</magenta>", flags)
+        );
+    }
+    execute!(io::stdout(), cursor::MoveTo(0, 1))?;
+    terminal::enable_raw_mode()?;
+    Ok(())
+}
+
+/// Prompts for the keys and options of a new index and creates it.
+/// Returns the message to report, or `None` if the prompt was cancelled.
+async fn create(collection: &Collection<Document>) -> Result<Option<String>> {
+    let keys = match ui::prompt_json(
+        "index keys, e.g. {email: 1}: ",
+        query::balance,
+        &[],
+        query::check(query::parse_document),
+    )? {
+        Some(keys) if !keys.trim().is_empty() => keys,
+        _ => return Ok(None),
+    };
+    let keys = match query::parse_document(keys.trim()) {
+        Ok(keys) => keys,
+        Err(e) => return Ok(Some(format!("invalid keys: {e}"))),
+    };
+    let options = match ui::prompt("options, e.g. unique, sparse, name=by_email: ")? {
+        Some(options) => options,
+        None => return Ok(None),
+    };
+    let options = match parse_options(&options) {
+        Ok(options) => options,
+        Err(e) => return Ok(Some(e)),
+    };
+
+    let model = IndexModel::builder().keys(keys).options(options).build();
+    Ok(Some(match collection.create_index(model, None).await {
+        Ok(created) => format!("created index {}", created.index_name),
+        Err(e) if is_duplicate_key(&e) => String::from(
+            "can't create a unique index: some documents share the same value for these keys",
+        ),
+        Err(e) => format!("failed to create the index: {}", describe_write_error(&e)),
+    }))
+}
+
+/// Drops the index called `name` after confirmation.
+/// Returns the message to report, or `None` if nothing was dropped.
+async fn drop(
+    collection: &Collection<Document>,
+    name: Option<String>,
+    confirm_timeout: Option<Duration>,
+) -> Result<Option<String>> {
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    if name == "_id_" {
+        return Ok(Some(String::from("the _id index can't be dropped")));
+    }
+    if !ui::confirm_within(&format!("drop index {name}?"), confirm_timeout)? {
+        return Ok(None);
+    }
+    Ok(Some(match collection.drop_index(&name, None).await {
+        Ok(()) => format!("dropped index {name}"),
+        Err(e) => format!("failed to drop the index: {}", describe_write_error(&e)),
+    }))
+}
+
+/// Parses comma separated index options: `unique`, `sparse` and `name=<name>`.
+fn parse_options(input: &str) -> Result<IndexOptions, String> {
+    let mut options = IndexOptions::default();
+    for option in input.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+        match option.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+            None if option == "unique" => options.unique = Some(true),
+            None if option == "sparse" => options.sparse = Some(true),
+            Some(("name", name)) => options.name = Some(name.to_string()),
+            _ => return Err(format!("unknown index option: {option}")),
+        }
+    }
+    Ok(options)
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    match *error.kind {
+        ErrorKind::Command(ref command) => command.code == 11000,
+        ErrorKind::Write(WriteFailure::WriteError(ref write)) => write.code == 11000,
+        _ => false,
+    }
+}
@@ -1,225 +1,4083 @@
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
-use color_print::{cprint, cprintln};
+use config::Config;
+use connection::{
+    describe_read_concern, describe_read_preference, describe_write_concern, describe_write_error,
+    ReadConcernMode, ReadPreferenceMode,
+};
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode},
+    event::{self, Event, KeyCode, KeyEvent},
     execute,
     terminal::{self, ClearType},
 };
-use futures::stream::TryStreamExt;
-use mongodb::{options::ClientOptions, Client, Database};
+use errors::is_unauthorized;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Bson, Document},
+    error::ErrorKind,
+    options::{
+        Acknowledgment, AggregateOptions, ClientOptions, Collation, CountOptions,
+        FindOneAndUpdateOptions, FindOneOptions, FindOptions, Hint, ReplaceOptions, ReturnDocument,
+        TagSet, UpdateOptions,
+    },
+    Client, Collection, Cursor, Database,
+};
+use render::{Dates, Indent, Json, Timezone};
 use serde_json::Value;
-use std::{io, process};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use style::{cformat, cprint, cprintln};
+
+mod codegen;
+mod config;
+mod connection;
+mod diff;
+mod editor;
+mod errors;
+mod eval;
+mod indexes;
+mod operations;
+mod oplog;
+mod profiler;
+mod query;
+mod render;
+mod repl;
+mod sharding;
+mod sidebar;
+mod style;
+mod table;
+mod tree;
+mod ui;
+mod watch;
+
+/// How long the selection has to rest on a collection before its first document is fetched.
+const PEEK_DELAY: Duration = Duration::from_millis(250);
+
+/// How many columns left/right scroll compact documents by.
+const H_SCROLL_STEP: usize = 8;
+
+/// Distinct values listed when grouping documents by a field.
+const MAX_GROUPS: usize = 1000;
+
+/// How many collections are searched at once when searching a whole database.
+const SEARCH_CONCURRENCY: usize = 4;
+
+/// Appended to the name of a collection for the one `--soft-delete` moves its documents to.
+const TRASH_SUFFIX: &str = "_trash";
+
+/// How long reads are retried for while a replica set elects a new primary.
+const FAILOVER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long to wait before retrying a read that failed during a failover.
+const FAILOVER_RETRY: Duration = Duration::from_secs(1);
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Connection string [default: $TERMONGO_URI, then $MONGODB_URI]
     #[arg(short, long)]
-    connect: String,
+    connect: Option<String>,
+    /// Write concern to use for mutations: `majority`, a number of nodes or a custom tag
+    #[arg(long, value_parser = connection::parse_acknowledgment)]
+    write_concern: Option<Acknowledgment>,
+    /// Read concern level, overriding the one in the connection string
+    #[arg(long)]
+    read_concern: Option<ReadConcernMode>,
+    /// Read preference, overriding the one in the connection string
+    #[arg(long)]
+    read_pref: Option<ReadPreferenceMode>,
+    /// Tags of the replica set members to read from, as `key:value,key:value`.
+    /// Repeat for fallback tag sets
+    #[arg(long, value_parser = connection::parse_tag_set)]
+    read_pref_tags: Vec<TagSet>,
+    /// PEM file with a client certificate and its key, to authenticate with MONGODB-X509
+    #[arg(long)]
+    tls_cert_key_file: Option<PathBuf>,
+    /// Name to identify the connection with in server logs and `currentOp`
+    /// [default: appName from the connection string, then `termongo`]
+    #[arg(long)]
+    app_name: Option<String>,
+    /// Time limit for queries, after which the server aborts them
+    #[arg(long)]
+    max_time_ms: Option<u64>,
+    /// Number of documents per page
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+    page_size: u64,
+    /// Prefix documents with their position in the results
+    #[arg(long)]
+    numbered: bool,
+    /// strftime format of dates in relaxed JSON
+    #[arg(long, default_value = "%Y-%m-%d %H:%M:%S%.3f %:z", value_parser = render::parse_date_format)]
+    date_format: String,
+    /// Time zone of dates in relaxed JSON
+    #[arg(long, value_enum, default_value_t = Timezone::Local)]
+    timezone: Timezone,
+    /// Leave `_id` out of displayed documents
+    #[arg(long)]
+    hide_id: bool,
+    /// Indentation of pretty documents: a number of spaces or `tab` [default: 2]
+    #[arg(long)]
+    indent: Option<Indent>,
+    /// Print plain text without colors [default: set if $NO_COLOR is]
+    #[arg(long)]
+    no_color: bool,
+    /// Refuse anything that would modify the deployment
+    #[arg(long)]
+    read_only: bool,
+    /// Ask for the exact name of a collection before dropping it, instead of just y/n
+    #[arg(long)]
+    strict_confirm: bool,
+    /// Seconds after which unanswered confirmations of destructive actions are cancelled
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    confirm_timeout: Option<u64>,
+    /// Let aggregations write temporary files when they run out of memory
+    #[arg(long)]
+    allow_disk_use: bool,
+    /// Move deleted documents to `<collection>_trash` without asking, instead of deleting them
+    /// for good
+    #[arg(long)]
+    soft_delete: bool,
+    /// Only send commands to this host of the connection string, e.g. to debug one of the
+    /// mongos routers of a sharded cluster
+    #[arg(long)]
+    mongos: Option<String>,
+    /// Open this database rather than listing them all
+    #[arg(long)]
+    database: Option<String>,
+    /// Open this collection of --database directly
+    #[arg(long, requires = "database")]
+    collection: Option<String>,
+    /// Run a query like `db.users.find({age: {$gt: 30}}).limit(5)`, print the results as JSON
+    /// and exit without starting the interface. Repeatable
+    #[arg(long)]
+    eval: Vec<String>,
+    /// Read queries like the ones --eval takes from a prompt and print their results, instead
+    /// of starting the interface
+    #[arg(long)]
+    repl: bool,
+    /// Count unfiltered collections exactly instead of estimating their size from metadata
+    #[arg(long)]
+    exact_counts: bool,
+    /// Ask before opening collections estimated to hold more documents than this, 0 to never ask
+    #[arg(long, default_value_t = 1_000_000)]
+    large_collection: u64,
+    /// Truncate strings longer than this many characters in the document view, 0 to never
+    #[arg(long, default_value_t = 500)]
+    max_string: usize,
+    /// Collapse subdocuments and arrays nested this deep in the pretty and document views, 0 to never
+    #[arg(long, default_value_t = 0)]
+    max_depth: usize,
+    /// Ping the server a few times, print the round trip times and exit
+    #[arg(long)]
+    ping: bool,
+}
+
+#[derive(Clone, Copy)]
+enum RenderMode {
+    /// One document per line, cut off at the right edge.
+    Compact,
+    Pretty,
+    /// One line per document with only `_id` and the summary fields.
+    Summary,
+    /// A column per field, for collections without nested documents.
+    Table,
+    /// Multi-line YAML documents.
+    Yaml,
+    /// A line per value, after its dotted path.
+    Flat,
+}
+
+/// The cursor of an aggregation shown in the collection view, open to read the next page from.
+struct Results {
+    cursor: Cursor<Document>,
+    /// Position in the results of `next`.
+    position: usize,
+    /// The document after the page, read to tell whether there's another one.
+    next: Document,
+}
+
+/// Everything that determines which documents the collection view fetches.
+#[derive(Clone)]
+struct Query {
+    database_name: String,
+    collection_name: String,
+    filter: Option<Document>,
+    hint: Option<Hint>,
+    pipeline: Option<Vec<Document>>,
+    sort_by_score: bool,
+    natural: Option<i32>,
+    skip: usize,
+    limit: Option<usize>,
+    page: usize,
+}
+
+/// A view as it was drawn, which the app goes back to when drawing another one is cancelled or
+/// fails, rather than keeping settings that don't match what's on screen.
+#[derive(Clone)]
+struct Shown {
+    query: Query,
+    database: Option<Database>,
+    database_search: Option<(Document, HashMap<String, u64>)>,
+    total: Option<Total>,
+    document: Option<Document>,
+}
+
+#[derive(Clone, Copy)]
+struct Total {
+    count: u64,
+    /// Whether `count` is the metadata-based estimate rather than an exact count.
+    estimated: bool,
 }
 
+#[derive(Clone, Copy)]
 enum State {
     Default,
     InsideDatabase,
     InsideCollection,
+    InsideDocument,
 }
 
 struct App {
     client: Client,
+    options: ClientOptions,
+    /// Commands sent to each host, to tell the routers of sharded clusters apart.
+    routers: Arc<connection::Routers>,
+    /// The host `--mongos` pins the client to.
+    mongos: Option<String>,
     state: State,
     list: Vec<(String, usize)>,
+    database_names: Vec<String>,
     collection_name: String,
     collection_list: Option<Vec<(String, usize)>>,
     database: Option<Database>,
     database_name: String,
-    previous_line: usize,
+    /// Cursor row last left in each state, indexed by `State as usize`, so backing out of a
+    /// document or collection returns to where the user was.
+    rows: [u16; 4],
+    filter: Option<Document>,
+    /// Index the server is told to use for the collection's queries.
+    hint: Option<Hint>,
+    /// Aggregation pipeline run on the matching documents instead of a plain find.
+    pipeline: Option<Vec<Document>>,
+    allow_disk_use: bool,
+    sort_by_score: bool,
+    /// Direction of the `$natural` sort finds use, 1 for insertion order and -1 for the
+    /// reverse, bypassing indexes.
+    natural: Option<i32>,
+    peek_pending: bool,
+    /// Filter searched for in every collection of the current database, with the number of
+    /// matches of the collections that had any.
+    database_search: Option<(Document, HashMap<String, u64>)>,
+    /// `timeseries` options of the time-series collections in the current database.
+    timeseries: HashMap<String, Document>,
+    documents: Vec<Document>,
+    /// Whether there are documents past the page.
+    has_more: bool,
+    /// The cursor of the aggregation shown, if there are more pages to read from it.
+    results: Option<Results>,
+    /// Whether the collection view is being drawn for the next page, which `results` may
+    /// hold.
+    paging_forward: bool,
+    render_mode: RenderMode,
+    json: Json,
+    dates: Dates,
+    /// Whether `_id` is left out of displayed documents. It's still fetched.
+    hide_id: bool,
+    /// Number of documents matching the filter, counted when the collection is opened.
+    total: Option<Total>,
+    /// Zero-based page of the collection being shown.
+    page: usize,
+    page_size: usize,
+    /// Documents skipped before the first page, set with `:skip`.
+    skip: usize,
+    /// Documents per page set with `:limit`, overriding `page_size`.
+    limit: Option<usize>,
+    /// Number of random documents `R` samples, set with `:sample`. Defaults to a page.
+    sample_size: Option<usize>,
+    /// Whether documents are prefixed with their position in the results.
+    numbered: bool,
+    /// How many columns compact documents are shifted to the left.
+    h_scroll: usize,
+    /// The document shown in `State::InsideDocument`.
+    document: Option<Document>,
+    tree: tree::Tree,
+    /// Server-side time limit for queries.
+    max_time: Option<Duration>,
+    /// How long the last query took, as seen by the client.
+    latency: Option<Duration>,
+    /// The last query the collection view ran, which `.` runs again.
+    last_query: Option<Query>,
+    /// The view on screen, once one has been drawn.
+    shown: Option<Shown>,
+    /// Whether unfiltered collections are counted exactly rather than estimated.
+    exact_counts: bool,
+    /// Estimated size above which opening a collection asks for confirmation, 0 for never.
+    large_collection: u64,
+    /// Length past which strings start out truncated in the document view, 0 for never.
+    max_string: usize,
+    /// Depth at which subdocuments and arrays start out collapsed, set with `:depth`, 0 for none.
+    max_depth: usize,
+    /// Whether updating the open document shows it as it was before the update, set with
+    /// `:return`, rather than after.
+    return_before: bool,
+    /// A document marked for comparison with the next one, and its namespace.
+    marked: Option<(String, Document)>,
+    indent: Indent,
+    read_only: bool,
+    strict_confirm: bool,
+    soft_delete: bool,
+    /// How long confirmations of destructive actions wait for an answer.
+    confirm_timeout: Option<Duration>,
+    config: Config,
+    /// Position among the open connections, e.g. `2/3`. Empty when there's only one.
+    tab: String,
+    /// Whether the host looks like production, going by `Config::production_hosts`.
+    production: bool,
 }
 
 impl App {
+    /// Connects to `connection_string` and lists its databases.
+    async fn open(connection_string: &str, args: &Args, config: Config) -> Result<App> {
+        let (client, options, routers) = connection::connect(connection_string, args).await?;
+        if args.mongos.is_some() && !sharding::is_mongos(&client).await.unwrap_or(true) {
+            client.shutdown().await;
+            return Err(anyhow!(
+                "--mongos pins one of the routers of a sharded cluster, the host isn't one"
+            ));
+        }
+        let production = connection::production_host(&options, &config.production_hosts);
+        if let Some(host) = &production {
+            // At startup, the terminal isn't in raw mode yet.
+            let raw = terminal::is_raw_mode_enabled()?;
+            terminal::enable_raw_mode()?;
+            let confirmed = ui::confirm(&format!("{host} looks like production, connect anyway?"));
+            if !raw {
+                terminal::disable_raw_mode()?;
+            }
+            if !confirmed? {
+                client.shutdown().await;
+                return Err(anyhow!(
+                    "didn't connect to {host}, which looks like production"
+                ));
+            }
+        }
+        let database_names = client.list_database_names(None, None).await?;
+        let production = production.is_some();
+        Ok(App::new(
+            client,
+            options,
+            routers,
+            database_names,
+            args,
+            config,
+            production,
+        ))
+    }
+
+    /// Shows the databases `database_names` of the connection `client` made.
+    fn new(
+        client: Client,
+        options: ClientOptions,
+        routers: Arc<connection::Routers>,
+        database_names: Vec<String>,
+        args: &Args,
+        config: Config,
+        production: bool,
+    ) -> App {
+        let mut app = App {
+            list: Vec::new(),
+            database_names,
+            client,
+            options,
+            routers,
+            mongos: args.mongos.clone(),
+            state: State::Default,
+            collection_name: String::new(),
+            collection_list: None,
+            database: None,
+            database_name: String::from("None"),
+            rows: [0, 1, 1, 1],
+            filter: None,
+            hint: None,
+            pipeline: None,
+            allow_disk_use: args.allow_disk_use,
+            sort_by_score: false,
+            natural: None,
+            peek_pending: false,
+            database_search: None,
+            timeseries: HashMap::new(),
+            documents: Vec::new(),
+            has_more: false,
+            results: None,
+            paging_forward: false,
+            render_mode: RenderMode::Compact,
+            json: Json::Relaxed,
+            dates: Dates {
+                format: args.date_format.clone(),
+                timezone: args.timezone,
+            },
+            hide_id: args.hide_id,
+            total: None,
+            page: 0,
+            page_size: args.page_size as usize,
+            skip: 0,
+            limit: None,
+            sample_size: None,
+            numbered: args.numbered,
+            h_scroll: 0,
+            document: None,
+            tree: tree::Tree::new(args.max_string, args.max_depth),
+            max_time: args.max_time_ms.map(Duration::from_millis),
+            latency: None,
+            last_query: None,
+            shown: None,
+            return_before: false,
+            marked: None,
+            exact_counts: args.exact_counts,
+            large_collection: args.large_collection,
+            max_string: args.max_string,
+            max_depth: args.max_depth,
+            indent: args.indent.or(config.indent).unwrap_or_default(),
+            read_only: args.read_only,
+            strict_confirm: args.strict_confirm,
+            soft_delete: args.soft_delete,
+            confirm_timeout: args.confirm_timeout.map(Duration::from_secs),
+            config,
+            tab: String::new(),
+            production,
+        };
+        let pinned = app.pinned(None);
+        app.list = layout(&app.database_names, &pinned).0;
+        app
+    }
+
     async fn change_state(&mut self, state: &State, database: Option<&str>) -> Result<()> {
-        terminal::disable_raw_mode()?;
+        let mut note = None;
+        self.rows[self.state as usize] = cursor::position()?.1;
+        // Going a level deeper opens something else than last time, so it starts at the top.
+        if *state as usize > self.state as usize {
+            self.rows[*state as usize] = 1;
+        }
         match state {
             State::Default => {
+                terminal::disable_raw_mode()?;
                 print!(
                     "{}{}",
                     cursor::MoveTo(0, 0),
                     terminal::Clear(ClearType::All),
                 );
-                for item in &self.list {
-                    cprintln!("<green>></green>  {}", item.0);
-                }
+                let pinned = self.pinned(None);
+                let (list, placed) = layout(&self.database_names, &pinned);
+                self.list = list;
+                print_list(&self.list, placed, |_| String::new());
             }
             State::InsideDatabase => {
-                print!(
-                    "{}{}",
-                    cursor::MoveTo(0, 0),
-                    terminal::Clear(ClearType::All),
-                );
-
                 let name = database.unwrap();
-                cprintln!("<yellow>/{}</yellow>", name);
-
                 let db = self.client.database(name);
 
-                let list: Vec<(_, _)> = db
-                    .list_collection_names(None)
-                    .await?
-                    .into_iter()
-                    .enumerate()
-                    .map(|(i, x)| (x, i))
-                    .collect();
+                // The driver's `CollectionSpecification` doesn't know about time-series
+                // collections, so the specifications are read as plain documents.
+                let query = || async {
+                    let cursor = db.list_collections(None, None).await?;
+                    cursor.with_type::<Document>().try_collect::<Vec<_>>().await
+                };
+                let specifications = match through_failover(query).await? {
+                    Some(Err(e)) if is_unauthorized(&e) => {
+                        return Box::pin(self.enter_unlisted_collection(db)).await;
+                    }
+                    Some(Ok(specifications)) => specifications,
+                    Some(Err(e)) => {
+                        return self.abandon(&format!("failed to list collections: {e}"))
+                    }
+                    None => return self.abandon("cancelled"),
+                };
 
-                for collection_name in &list {
-                    cprint!("<green>></green>  {}\n", collection_name.0);
+                let mut names = Vec::new();
+                self.timeseries.clear();
+                for specification in specifications {
+                    let name = match specification.get_str("name") {
+                        Ok(name) if !name.starts_with("system.buckets.") => name.to_string(),
+                        _ => continue,
+                    };
+                    if specification.get_str("type") == Ok("timeseries") {
+                        let options = specification
+                            .get_document("options")
+                            .and_then(|o| o.get_document("timeseries"))
+                            .cloned()
+                            .unwrap_or_default();
+                        self.timeseries.insert(name.clone(), options);
+                    }
+                    names.push(name);
+                }
+                if let Some((_, matches)) = &self.database_search {
+                    names.retain(|name| matches.contains_key(name));
                 }
+                let pinned = self.pinned(Some(name));
+                let (list, placed) = layout(&names, &pinned);
 
-                self.collection_list = Some(list);
-                self.database = Some(db);
-            }
-            State::InsideCollection => {
+                terminal::disable_raw_mode()?;
                 print!(
                     "{}{}",
                     cursor::MoveTo(0, 0),
                     terminal::Clear(ClearType::All),
                 );
+                cprint!("<yellow>/{}</yellow>", name);
+                match &self.database_search {
+                    Some((filter, _)) if names.is_empty() => {
+                        cprintln!(" <cyan>no matches for {}</cyan>", filter)
+                    }
+                    Some((filter, _)) => cprintln!(" <cyan>matching {}</cyan>", filter),
+                    None => println!(),
+                }
 
+                print_list(&list, placed, |name| {
+                    let mut suffix = String::new();
+                    if self.timeseries.contains_key(name) {
+                        suffix.push_str(&cformat!(" <magenta>[timeseries]</magenta>"));
+                    }
+                    if let Some(count) = self
+                        .database_search
+                        .as_ref()
+                        .and_then(|(_, matches)| matches.get(name))
+                    {
+                        suffix.push_str(&cformat!(" <cyan>{} matches</cyan>", count));
+                    }
+                    suffix
+                });
+
+                self.collection_list = Some(list);
+                self.database = Some(db);
+            }
+            State::InsideCollection => {
                 let collection = self
                     .database
                     .as_ref()
                     .unwrap()
-                    .collection::<Value>(database.as_ref().expect("No data."));
+                    .collection::<Document>(database.as_ref().expect("No data."));
 
-                let cursor = match collection.find(None, None).await {
-                    Ok(cursor) => cursor,
-                    Err(_) => return Err(anyhow!("No cursor found.")),
-                };
+                let mut options = FindOptions::builder()
+                    .max_time(self.max_time)
+                    .collation(self.collation())
+                    .hint(self.hint.clone())
+                    .skip(self.first_index() as u64)
+                    .limit(self.page_limit() as i64 + 1)
+                    .build();
+                if self.sort_by_score {
+                    let score = doc! { "textScore": { "$meta": "textScore" } };
+                    options.projection = Some(score.clone());
+                    options.sort = Some(score);
+                } else if let Some(direction) = self.natural {
+                    options.sort = Some(doc! { "$natural": direction });
+                }
 
-                let data = cursor.try_collect().await.unwrap_or_else(|_| vec![]);
+                // The next page of an aggregation is read from where its cursor stopped, rather
+                // than running the pipeline again.
+                let held = self.results.take().filter(|results| {
+                    self.paging_forward && results.position == self.first_index()
+                });
+                self.paging_forward = false;
 
-                cprintln!(
-                    "<yellow>{}/{}</yellow>",
-                    self.database_name,
-                    database.unwrap()
-                );
-                for i in data {
-                    println!("{i}");
+                let query = || async {
+                    let total = match self.total {
+                        Some(total) => Some(total),
+                        None if self.pipeline.is_some() => None,
+                        None => count(&collection, self.filter.clone(), self, self.exact_counts)
+                            .await
+                            .ok(),
+                    };
+                    if let Some(pipeline) = &self.pipeline {
+                        // Without a $limit, the cursor can go on to the next pages.
+                        let stages = self
+                            .filter
+                            .iter()
+                            .map(|filter| doc! { "$match": filter })
+                            .chain(pipeline.iter().cloned())
+                            .chain([doc! { "$skip": self.first_index() as i64 }]);
+                        let options = AggregateOptions::builder()
+                            .allow_disk_use(self.allow_disk_use)
+                            .max_time(self.max_time)
+                            .collation(self.collation())
+                            .hint(self.hint.clone())
+                            .build();
+                        let mut cursor = collection.aggregate(stages, options).await?;
+                        let mut data = Vec::new();
+                        fill(&mut cursor, &mut data, self.page_limit() + 1).await?;
+                        return Ok((data, total, Some(cursor)));
+                    }
+                    let cursor = collection
+                        .find(self.filter.clone(), options.clone())
+                        .await?;
+                    let data = cursor.try_collect::<Vec<_>>().await?;
+                    Ok((data, total, None))
+                };
+                let started = Instant::now();
+                let mut result = match held {
+                    Some(Results {
+                        mut cursor, next, ..
+                    }) => {
+                        let (total, limit) = (self.total, self.page_limit() + 1);
+                        let read = async move {
+                            let mut data = vec![next];
+                            fill(&mut cursor, &mut data, limit).await?;
+                            Ok((data, total, Some(cursor)))
+                        };
+                        ui::cancellable(read).await?
+                    }
+                    None => through_failover(query).await?,
+                };
+                // Cursors the server dropped, e.g. after the session sat idle, are queried anew.
+                if matches!(&result, Some(Err(e)) if is_cursor_not_found(e)) {
+                    note = Some("cursor expired, re-queried");
+                    result = through_failover(query).await?;
+                }
+                let (mut data, total, cursor) = match result {
+                    Some(Ok(result)) => result,
+                    Some(Err(e)) => return self.abandon(&self.describe_fetch_error(&e)),
+                    None => return self.abandon("cancelled"),
+                };
+
+                self.latency = Some(started.elapsed());
+                // One more document than fits on the page is fetched to tell whether there's
+                // another page, without counting.
+                self.has_more = data.len() > self.page_limit();
+                let next = data.split_off(self.page_limit().min(data.len())).pop();
+                // Exhausted cursors are dropped, there's nothing left to read from them.
+                if let (Some(cursor), Some(next)) = (cursor, next) {
+                    self.results = Some(Results {
+                        cursor,
+                        position: self.first_index() + self.page_limit(),
+                        next,
+                    });
                 }
+                self.documents = data;
+                self.total = total;
+                self.h_scroll = 0;
+                self.last_query = Some(self.query());
+
+                terminal::disable_raw_mode()?;
+                self.draw_collection()?;
+            }
+            State::InsideDocument => {
+                terminal::disable_raw_mode()?;
+                self.draw_document()?;
             }
         }
+        self.state = *state;
+        self.shown = Some(Shown {
+            query: self.query(),
+            database: self.database.clone(),
+            database_search: self.database_search.clone(),
+            total: self.total,
+            document: self.document.clone(),
+        });
+        // Lists are rebuilt on every visit and may come back in another order, so the cursor
+        // goes back to the database or collection by name rather than by row.
+        let row = match state {
+            State::Default => row_of(&self.list, &self.database_name),
+            State::InsideDatabase => self
+                .collection_list
+                .as_ref()
+                .and_then(|list| row_of(list, &self.collection_name))
+                .map(|row| row + 1),
+            _ => None,
+        };
         execute!(
             io::stdout(),
-            cursor::MoveToRow(self.previous_line as u16 + 1)
+            cursor::MoveToRow(row.map_or(self.rows[*state as usize], |row| row as u16))
         )?;
         terminal::enable_raw_mode()?;
+        self.draw_footer()?;
+        match note {
+            Some(note) => ui::status(note),
+            None => Ok(()),
+        }
+    }
+
+    /// What decides the documents the collection view fetches.
+    fn query(&self) -> Query {
+        Query {
+            database_name: self.database_name.clone(),
+            collection_name: self.collection_name.clone(),
+            filter: self.filter.clone(),
+            hint: self.hint.clone(),
+            pipeline: self.pipeline.clone(),
+            sort_by_score: self.sort_by_score,
+            natural: self.natural,
+            skip: self.skip,
+            limit: self.limit,
+            page: self.page,
+        }
+    }
+
+    fn set_query(&mut self, query: Query) {
+        self.database_name = query.database_name;
+        self.collection_name = query.collection_name;
+        self.filter = query.filter;
+        self.hint = query.hint;
+        self.pipeline = query.pipeline;
+        self.sort_by_score = query.sort_by_score;
+        self.natural = query.natural;
+        self.skip = query.skip;
+        self.limit = query.limit;
+        self.page = query.page;
+    }
+
+    /// Tells why the view asked for isn't shown, going back to the settings of the one still on
+    /// screen.
+    fn abandon(&mut self, message: &str) -> Result<()> {
+        self.restore_shown();
+        ui::status(message)
+    }
+
+    fn restore_shown(&mut self) {
+        if let Some(shown) = self.shown.clone() {
+            self.set_query(shown.query);
+            self.database = shown.database;
+            self.database_search = shown.database_search;
+            self.total = shown.total;
+            self.document = shown.document;
+        }
+        self.paging_forward = false;
+    }
+
+    fn draw_collection(&self) -> Result<()> {
+        let (columns, _) = terminal::size()?;
+        print!(
+            "{}{}",
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All),
+        );
+        cprint!(
+            "<yellow>{}/{}</yellow>",
+            self.database_name,
+            self.collection_name
+        );
+        if let Some(filter) = &self.filter {
+            match filter.get_document("$text") {
+                Ok(search) => cprint!(" <cyan>$text: {}</cyan>", search),
+                Err(_) => cprint!(" <cyan>filter: {}</cyan>", filter),
+            }
+        }
+        if let Some(options) = self.timeseries.get(&self.collection_name) {
+            cprint!(" <magenta>[timeseries]</magenta>");
+            for (label, key) in [
+                ("time", "timeField"),
+                ("meta", "metaField"),
+                ("granularity", "granularity"),
+            ] {
+                if let Ok(value) = options.get_str(key) {
+                    cprint!(" <magenta>{}: {}</magenta>", label, value);
+                }
+            }
+        }
+        if let Some(pipeline) = &self.pipeline {
+            cprint!(" <cyan>pipeline: {} stages</cyan>", pipeline.len());
+            if self.allow_disk_use {
+                cprint!(" <cyan>allowDiskUse</cyan>");
+            }
+        }
+        if let Some(collation) = self.collation() {
+            cprint!(" <cyan>collation: {}</cyan>", collation.locale);
+            if let Some(strength) = collation.strength {
+                cprint!(" <cyan>{}</cyan>", u32::from(strength));
+            }
+        }
+        match &self.hint {
+            Some(Hint::Name(name)) => cprint!(" <cyan>hint: {}</cyan>", name),
+            Some(Hint::Keys(keys)) => cprint!(" <cyan>hint: {}</cyan>", keys),
+            _ => {}
+        }
+        if let Some(max_time) = self.max_time {
+            cprint!(" <cyan>max {}ms</cyan>", max_time.as_millis());
+        }
+        match self.natural {
+            _ if self.pipeline.is_some() || self.sort_by_score => {}
+            Some(1) => cprint!(" <cyan>$natural ↑</cyan>"),
+            Some(_) => cprint!(" <cyan>$natural ↓</cyan>"),
+            None => {}
+        }
+        if self.max_depth > 0 && matches!(self.render_mode, RenderMode::Pretty) {
+            cprint!(" <cyan>depth {}</cyan>", self.max_depth);
+        }
+        if self.h_scroll > 0 {
+            cprint!(" <cyan>→{}</cyan>", self.h_scroll);
+        }
+        if self.skip > 0 {
+            cprint!(" <cyan>skip {}</cyan>", self.skip);
+        }
+        if let Some(limit) = self.limit {
+            cprint!(" <cyan>limit {}</cyan>", limit);
+        }
+        cprint!(" <cyan>page {}</cyan>", self.page + 1);
+        match self.has_more {
+            true => cprint!(" <cyan>▼ more</cyan>"),
+            false => cprint!(" <cyan>(end)</cyan>"),
+        }
+        match self.total {
+            Some(total) if total.estimated => {
+                cprint!(
+                    " <cyan>— ~{} total (estimated, E to count)</cyan>",
+                    total.count
+                )
+            }
+            Some(total) => cprint!(" <cyan>— {} total (exact)</cyan>", total.count),
+            None => {}
+        }
+        let table = match self.render_mode {
+            RenderMode::Table => table::render(
+                &self.documents,
+                self.json,
+                &self.dates,
+                self.hide_id,
+                self.hidden_columns(),
+            ),
+            _ => None,
+        };
+        if matches!(self.render_mode, RenderMode::Table) && table.is_none() {
+            cprint!(" <magenta>(too nested for a table)</magenta>");
+        }
+        println!();
+
+        if let Some(lines) = table {
+            for line in lines {
+                let line: String = line
+                    .chars()
+                    .skip(self.h_scroll)
+                    .take(columns as usize)
+                    .collect();
+                println!("{line}");
+            }
+            return Ok(());
+        }
+
+        for (i, document) in self.documents.iter().enumerate() {
+            let mut document = document.clone();
+            let mut width = columns as usize;
+            // Flattened documents are told apart by a blank line between them.
+            if matches!(self.render_mode, RenderMode::Flat) && i > 0 {
+                println!();
+            }
+            if self.numbered {
+                let number = format!("#{} ", self.first_index() + i + 1);
+                width = width.saturating_sub(number.chars().count());
+                cprint!("<dim>{}</dim>", number);
+            }
+            if self.is_bookmarked(&document) {
+                width = width.saturating_sub(2);
+                cprint!("<yellow>★ </yellow>");
+            }
+            if self.sort_by_score {
+                if let Some(score) = document.remove("textScore") {
+                    let score = format!("({:.2}) ", score.as_f64().unwrap_or_default());
+                    width = width.saturating_sub(score.chars().count());
+                    cprint!("<cyan>{}</cyan>", score);
+                }
+            }
+            if let RenderMode::Summary = self.render_mode {
+                let line: String = self.summarize(&document).chars().take(width).collect();
+                println!("{line}");
+                continue;
+            }
+            if self.hide_id {
+                document.remove("_id");
+            }
+            if let RenderMode::Flat = self.render_mode {
+                for (path, value) in render::flatten(&document) {
+                    let value = &self.displayed(&doc! { "value": value })["value"];
+                    cprintln!("<green>{}</green>: {}", path, value);
+                }
+                continue;
+            }
+            let document = self.displayed(&document);
+            match self.render_mode {
+                // Tables fall back to compact documents when they don't fit.
+                RenderMode::Compact | RenderMode::Table => {
+                    let line: String = document
+                        .to_string()
+                        .chars()
+                        .skip(self.h_scroll)
+                        .take(width)
+                        .collect();
+                    println!("{line}");
+                }
+                RenderMode::Pretty => println!(
+                    "{}",
+                    render::pretty_within(&document, self.indent, self.max_depth)?
+                ),
+                RenderMode::Yaml => print!("---\n{}", render::yaml(&document)?),
+                RenderMode::Summary | RenderMode::Flat => {}
+            }
+        }
         Ok(())
     }
-}
 
-async fn connect(connection_string: String) -> Result<Client> {
-    let client_options = ClientOptions::parse(connection_string).await;
-    match client_options {
-        Ok(c) => {
-            let client: Client = Client::with_options(c)?;
-            Ok(client)
+    /// `_id` and the summary fields of `document`: the ones set with `:summary`, or else its
+    /// first two other fields.
+    fn summarize(&self, document: &Document) -> String {
+        let mut parts = Vec::new();
+        if !self.hide_id {
+            parts.push(copyable(
+                document.get("_id").unwrap_or(&Bson::Null),
+                self.json,
+            ));
         }
-        Err(e) => Err(anyhow!("Invalid connection string: {}", e)),
+        for field in self.summary_fields(document) {
+            let value = value_at(document, &field)
+                .map(|value| copyable(&value, self.json))
+                .unwrap_or_else(|| String::from("-"));
+            parts.push(format!("{field}: {value}"));
+        }
+        parts.join("  ")
     }
-}
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
-    let client = connect(args.connect).await.unwrap();
-    let l = client.list_database_names(None, None).await?;
-    let list: Vec<(_, _)> = l.into_iter().enumerate().map(|(i, x)| (x, i)).collect();
-
-    let mut app = App {
-        list,
-        client,
-        state: State::Default,
-        collection_name: String::new(),
-        collection_list: None,
-        database: None,
-        database_name: String::from("None"),
-        previous_line: 1,
-    };
+    /// The fields set with `:summary` for the collection, or else the first two fields of
+    /// `document` other than `_id`.
+    fn summary_fields(&self, document: &Document) -> Vec<String> {
+        match self
+            .config
+            .summaries
+            .get(&self.host_key())
+            .and_then(|summaries| summaries.get(&self.namespace()))
+        {
+            Some(fields) => fields.clone(),
+            None => document
+                .keys()
+                .filter(|key| *key != "_id")
+                .take(2)
+                .cloned()
+                .collect(),
+        }
+    }
 
-    let mut stdout = io::stdout();
-    terminal::enable_raw_mode().context("failed to put terminal in raw mode")?;
-    terminal::disable_raw_mode()?;
-    print!(
-        "{}{}",
-        cursor::MoveToRow(0),
-        terminal::Clear(ClearType::All),
-    );
-    for item in &app.list {
-        cprintln!("<green>></green>  {}", item.0);
+    /// The columns hidden from the table view of the collection.
+    fn hidden_columns(&self) -> &[String] {
+        self.config
+            .hidden_columns
+            .get(&self.host_key())
+            .and_then(|columns| columns.get(&self.namespace()))
+            .map_or(&[], Vec::as_slice)
     }
 
-    terminal::enable_raw_mode()?;
+    /// Shows or hides columns of the table view, picked among the fields of the page.
+    fn pick_columns(&mut self) -> Result<()> {
+        if !matches!(self.render_mode, RenderMode::Table) {
+            return ui::status("columns are picked in the table view, T shows it");
+        }
+        let hidden = self.hidden_columns().to_vec();
+        // Hidden columns of documents on other pages stay hidden.
+        let mut columns: Vec<String> = table::columns(&self.documents, self.hide_id, &[])
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let elsewhere: Vec<String> = hidden
+            .iter()
+            .filter(|column| !columns.contains(column))
+            .cloned()
+            .collect();
+        columns.extend(elsewhere);
+        let mut shown: Vec<bool> = columns.iter().map(|c| !hidden.contains(c)).collect();
+        let title = cformat!("<yellow>columns of {}</yellow>", self.namespace());
+        if !ui::toggle(&title, &columns, &mut shown)? {
+            return self.repaint();
+        }
 
-    loop {
-        if let Event::Key(event) = event::read().context("failed to read a terminal event")? {
-            match app.state {
-                State::Default => match event.code {
-                    KeyCode::Char('q') => {
-                        terminal::disable_raw_mode()?;
-                        process::exit(0)
-                    }
-                    KeyCode::Char('j') => execute!(stdout, cursor::MoveDown(1))?,
-                    KeyCode::Char('k') => execute!(stdout, cursor::MoveUp(1))?,
-                    KeyCode::Enter => {
-                        let index = cursor::position()?.1 as usize;
-                        for item in &app.list {
-                            if item.1 == index {
-                                app.previous_line = index;
-                                let matc = String::from(&item.0);
-                                app.state = State::InsideDatabase;
-                                app.database_name = matc.clone();
-                                app.change_state(&State::InsideDatabase, Some(&matc))
-                                    .await?;
-                                break;
-                            }
-                        }
-                    }
-                    _ => {}
-                },
-                State::InsideDatabase => match event.code {
-                    KeyCode::Char('j') => execute!(stdout, cursor::MoveDown(1))?,
-                    KeyCode::Char('k') => execute!(stdout, cursor::MoveUp(1))?,
-                    KeyCode::Char('q') => {
-                        app.state = State::Default;
-                        app.change_state(&State::Default, Some(&String::from("none")))
-                            .await?;
-                    }
-                    KeyCode::Enter => {
-                        let index: usize = (cursor::position()?.1 - 1).into();
-                        let collection = app.collection_list.take().expect("No collection found.");
-                        for i in &collection {
-                            let (item, item_index) = i;
-                            if item_index == &index {
-                                app.previous_line = index;
-                                app.state = State::InsideCollection;
-                                app.collection_name = item.to_string();
-                                app.change_state(&State::InsideCollection, Some(item))
-                                    .await?;
-                            }
+        let hidden: Vec<String> = columns
+            .into_iter()
+            .zip(shown)
+            .filter(|(_, shown)| !shown)
+            .map(|(column, _)| column)
+            .collect();
+        let (host, namespace) = (self.host_key(), self.namespace());
+        let saved = self.update_config(|config| {
+            let columns = config.hidden_columns.entry(host).or_default();
+            match hidden.is_empty() {
+                true => columns.remove(&namespace),
+                false => columns.insert(namespace, hidden),
+            };
+        });
+        self.h_scroll = 0;
+        self.repaint()?;
+        if let Err(e) = saved {
+            return ui::status(&format!("failed to save the columns: {e}"));
+        }
+        Ok(())
+    }
+
+    /// Copies the documents of the page to the clipboard as a Markdown table, with only the
+    /// summary fields in the summary view.
+    fn copy_markdown_table(&self) -> Result<()> {
+        if self.documents.is_empty() {
+            return ui::status("no documents to copy");
+        }
+        let documents: Vec<Document> = match self.render_mode {
+            RenderMode::Summary => self
+                .documents
+                .iter()
+                .map(|document| {
+                    let mut summary = Document::new();
+                    summary.insert("_id", document.get("_id").cloned().unwrap_or(Bson::Null));
+                    for field in self.summary_fields(document) {
+                        if let Some(value) = value_at(document, &field) {
+                            summary.insert(field, value);
                         }
                     }
-                    _ => {}
-                },
-                State::InsideCollection => match event.code {
-                    KeyCode::Char('j') => execute!(stdout, cursor::MoveDown(1))?,
-                    KeyCode::Char('k') => execute!(stdout, cursor::MoveUp(1))?,
-                    KeyCode::Char('q') => {
-                        app.state = State::InsideDatabase;
-                        app.change_state(&State::InsideDatabase, Some(&app.database_name.clone()))
-                            .await?;
-                    }
-                    _ => {}
-                },
+                    summary
+                })
+                .collect(),
+            _ => self.documents.clone(),
+        };
+        let hidden = match self.render_mode {
+            RenderMode::Table => self.hidden_columns(),
+            _ => &[],
+        };
+        let table = table::markdown(&documents, self.json, &self.dates, self.hide_id, hidden);
+        ui::copy(&table)?;
+        ui::status(&format!(
+            "copied {} document(s) as a Markdown table",
+            documents.len()
+        ))
+    }
+
+    fn draw_document(&self) -> Result<()> {
+        let id = self.document.as_ref().and_then(|d| d.get("_id")).cloned();
+        let document = self.shown_document();
+        print!(
+            "{}{}",
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All),
+        );
+        cprint!(
+            "<yellow>{}/{}</yellow> <cyan>{}</cyan>",
+            self.database_name,
+            self.collection_name,
+            self.json.to_value(&doc! { "_id": id })["_id"]
+        );
+        match self.max_depth {
+            0 => println!(),
+            depth => cprintln!(" <cyan>depth {}</cyan>", depth),
+        }
+        for line in self.tree.render(&document, self.indent) {
+            println!("{}", line.text);
+        }
+        Ok(())
+    }
+
+    /// A tree for a document just opened, with everything collapsed that starts out collapsed.
+    fn new_tree(&self) -> tree::Tree {
+        tree::Tree::new(self.max_string, self.max_depth)
+    }
+
+    /// The open document as it's rendered in the document view.
+    fn shown_document(&self) -> Value {
+        let mut document = self.document.clone().expect("No document.");
+        if self.hide_id {
+            document.remove("_id");
+        }
+        self.displayed(&document)
+    }
+
+    /// `document` in the chosen flavour of JSON, with formatted dates if it's relaxed.
+    fn displayed(&self, document: &Document) -> Value {
+        let mut value = self.json.to_value(document);
+        if let Json::Relaxed = self.json {
+            self.dates.apply(&mut value);
+        }
+        value
+    }
+
+    fn toggle_selected_node(&mut self) -> Result<()> {
+        let lines = self.tree.render(&self.shown_document(), self.indent);
+        let index = (cursor::position()?.1 as usize).checked_sub(1);
+        if let Some(line) = index.and_then(|i| lines.get(i)) {
+            self.tree.toggle(line);
+            self.repaint()?;
+        }
+        Ok(())
+    }
+
+    /// Copies the value of the highlighted field to the clipboard.
+    fn copy_selected_value(&self) -> Result<()> {
+        let document = self.document.as_ref().expect("No document.");
+        let lines = self.tree.render(&self.shown_document(), self.indent);
+        let index = (cursor::position()?.1 as usize).checked_sub(1);
+        let value = match index.and_then(|i| lines.get(i)) {
+            Some(line) => value_at(document, &line.path),
+            None => return Ok(()),
+        };
+        match value {
+            Some(value) => {
+                ui::copy(&copyable(&value, self.json))?;
+                ui::status("copied the value to the clipboard")
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Prompts for a new value of the highlighted field, starting from the current one, and sets
+    /// just that field. Numbers stay numbers, strings strings and so on.
+    async fn set_selected_field(&mut self) -> Result<()> {
+        if self.read_only {
+            return ui::status("read-only mode");
+        }
+        let document = self.document.as_ref().expect("No document.");
+        let lines = self.tree.render(&self.shown_document(), self.indent);
+        let index = (cursor::position()?.1 as usize).checked_sub(1);
+        // The wrappers of canonical extended JSON belong to the field.
+        let path = match index.and_then(|i| lines.get(i)) {
+            Some(line) => line
+                .path
+                .split('.')
+                .take_while(|key| !key.starts_with('$'))
+                .collect::<Vec<_>>()
+                .join("."),
+            None => return Ok(()),
+        };
+        if path.is_empty() {
+            return ui::status("select a field to set");
+        }
+        if path == "_id" || path.starts_with("_id.") {
+            return ui::status("_id can't be changed");
+        }
+        let current = match value_at(document, &path) {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        let input =
+            match ui::prompt_with_input(&format!("{path}: "), &copyable(&current, self.json))? {
+                Some(input) => input,
+                None => return Ok(()),
+            };
+        let value = match typed_value(&current, &input) {
+            Ok(value) => value,
+            Err(e) => return ui::status(&format!("invalid value: {e}")),
+        };
+        if value == current {
+            return ui::status("no changes");
+        }
+
+        let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+        let filter = doc! { "_id": id };
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let options = UpdateOptions::builder().collation(self.collation()).build();
+        let update = doc! { "$set": { path.clone(): value } };
+        let updated =
+            match ui::cancellable(collection.update_one(filter.clone(), update, options)).await? {
+                Some(Ok(result)) => result,
+                Some(Err(e)) => {
+                    return ui::status(&format!("failed to update: {}", describe_write_error(&e)))
+                }
+                None => return ui::status("cancelled, the update may still have been applied"),
+            };
+        if updated.matched_count == 0 {
+            return ui::status("the document was deleted in the meantime");
+        }
+        if let Some(document) = collection.find_one(filter, None).await? {
+            self.document = Some(document);
+        }
+        self.redraw().await?;
+        ui::status(&format!(
+            "set {path}, modified {} document(s)",
+            updated.modified_count
+        ))
+    }
+
+    /// Opens the document the highlighted field refers to, looking its value up as an `_id`
+    /// in a collection named after the field unless another one is given.
+    async fn follow_reference(&mut self) -> Result<()> {
+        let document = self.document.as_ref().expect("No document.");
+        let lines = self.tree.render(&self.shown_document(), self.indent);
+        let index = (cursor::position()?.1 as usize).checked_sub(1);
+        let path = match index.and_then(|i| lines.get(i)) {
+            Some(line) => line.path.clone(),
+            None => return Ok(()),
+        };
+        let id = match value_at(document, &path) {
+            Some(Bson::Document(_) | Bson::Array(_)) | None => {
+                return ui::status("select a field holding an id")
+            }
+            Some(id) => id,
+        };
+
+        // Items of an array of references are named after the array, and the wrappers of
+        // canonical extended JSON after the field.
+        let field = path
+            .rsplit('.')
+            .find(|key| key.parse::<usize>().is_err() && !key.starts_with('$'))
+            .unwrap_or_default();
+        let guess = referenced_collection(field);
+        let target = match ui::prompt(&format!("look up in collection ({guess}): "))? {
+            Some(input) if input.trim().is_empty() => guess,
+            Some(input) => input.trim().to_string(),
+            None => return Ok(()),
+        };
+        let names: Vec<String> = self
+            .collection_list
+            .iter()
+            .flatten()
+            .map(|(name, _)| name.clone())
+            .collect();
+        let target = matching_name(&names, &target);
+
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&target);
+        let options = FindOneOptions::builder().max_time(self.max_time).build();
+        let query = collection.find_one(doc! { "_id": id.clone() }, options);
+        let found = match ui::cancellable(query).await? {
+            Some(Err(e)) if is_max_time_expired(&e) => return self.report_max_time_expired(),
+            Some(found) => found?,
+            None => return ui::status("cancelled"),
+        };
+        match found {
+            Some(found) => {
+                self.reset_collection_view();
+                self.collection_name = target;
+                self.document = Some(found);
+                self.tree = self.new_tree();
+                self.change_state(&State::InsideDocument, None).await
+            }
+            None => ui::status(&format!(
+                "no document in {target} with _id {}",
+                copyable(&id, self.json)
+            )),
+        }
+    }
+
+    /// Edits the open document, in an external editor if one is set, and replaces it with the result once the
+    /// changes are confirmed.
+    async fn edit_document(&mut self) -> Result<()> {
+        if self.read_only {
+            return ui::status("read-only mode");
+        }
+        let original = self.document.clone().expect("No document.");
+        let text = render::pretty(&self.json.to_value(&original), self.indent)?;
+        let validate = query::check(query::parse_document);
+        let edited = ui::edit(&text, "edit the document", &validate).and_then(|text| {
+            text.map(|text| {
+                query::parse_document(&text).map_err(|e| anyhow!("invalid document: {e}"))
+            })
+            .transpose()
+        });
+        let mut edited = match edited {
+            Ok(Some(edited)) => edited,
+            Ok(None) => {
+                self.redraw().await?;
+                return ui::status("not edited");
+            }
+            Err(e) => {
+                self.redraw().await?;
+                return ui::status(&e.to_string());
+            }
+        };
+
+        if matches!(self.json, Json::Relaxed) {
+            diff::keep_number_types(&original, &mut edited);
+        }
+        let mut changes = diff::diff(&original, &edited, self.json);
+        if changes.is_empty() {
+            self.redraw().await?;
+            return ui::status("no changes");
+        }
+        if edited.get("_id") != original.get("_id") {
+            self.redraw().await?;
+            return ui::status("_id can't be changed");
+        }
+        changes.insert(
+            0,
+            cformat!("<yellow>changes to {}</yellow>", self.namespace()),
+        );
+        changes.push(String::new());
+        changes.push(String::from("y: save, esc: discard"));
+        let confirmed = ui::panel(&changes, &['y'])? == Some('y');
+        if !confirmed {
+            self.redraw().await?;
+            return ui::status("discarded the changes");
+        }
+
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let id = original.get("_id").cloned().unwrap_or(Bson::Null);
+        let replaced = collection
+            .replace_one(doc! { "_id": id }, &edited, None)
+            .await;
+        let message = match replaced {
+            Ok(result) if result.matched_count == 0 => {
+                String::from("the document was deleted in the meantime")
+            }
+            Ok(_) => {
+                self.document = Some(edited);
+                String::from("saved")
+            }
+            Err(e) => format!("failed to save: {}", describe_write_error(&e)),
+        };
+        self.redraw().await?;
+        ui::status(&message)
+    }
+
+    /// Prompts for an update and applies it to the open document atomically, then shows the
+    /// document the server returned, as it was before or after the update depending on
+    /// `return_before`.
+    async fn update_document(&mut self) -> Result<()> {
+        if self.read_only {
+            return ui::status("read-only mode");
+        }
+        let update = match ui::prompt_json(
+            "update this document, e.g. {$inc: {stock: -1}} or a pipeline: ",
+            query::balance,
+            &query::field_paths(self.document.as_slice()),
+            query::check(query::parse_update),
+        )? {
+            Some(input) if !input.trim().is_empty() => input,
+            _ => return Ok(()),
+        };
+        let update = match query::parse_update(update.trim()) {
+            Ok(update) => update,
+            Err(e) => return ui::status(&format!("invalid update: {e}")),
+        };
+
+        let id = self.document.as_ref().and_then(|d| d.get("_id")).cloned();
+        let filter = doc! { "_id": id.unwrap_or(Bson::Null) };
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(match self.return_before {
+                true => ReturnDocument::Before,
+                false => ReturnDocument::After,
+            })
+            .collation(self.collation())
+            .build();
+        let updated =
+            ui::cancellable(collection.find_one_and_update(filter.clone(), update, options));
+        let returned = match updated.await? {
+            Some(Ok(Some(returned))) => returned,
+            Some(Ok(None)) => return ui::status("the document was deleted in the meantime"),
+            Some(Err(e)) => {
+                return ui::status(&format!("failed to update: {}", describe_write_error(&e)))
+            }
+            None => return ui::status("cancelled, the update may still have been applied"),
+        };
+
+        let title = match self.return_before {
+            true => "the document before the update",
+            false => "the document after the update",
+        };
+        self.show_returned(title, &returned)?;
+        self.document = match self.return_before {
+            true => collection.find_one(filter, None).await.ok().flatten(),
+            false => Some(returned),
+        };
+        match self.document {
+            Some(_) => self.redraw().await,
+            None => {
+                self.change_state(
+                    &State::InsideCollection,
+                    Some(&self.collection_name.clone()),
+                )
+                .await
+            }
+        }
+    }
+
+    /// Deletes the open document atomically once the user confirms it, shows the document the
+    /// server deleted and goes back to the collection.
+    async fn delete_document(&mut self) -> Result<()> {
+        if self.read_only {
+            return ui::status("read-only mode");
+        }
+        // Documents deleted from a trash are gone for good.
+        if self.soft_delete && !self.collection_name.ends_with(TRASH_SUFFIX) {
+            return self.trash_document().await;
+        }
+        if !ui::confirm_within("delete this document?", self.confirm_timeout)? {
+            return ui::status("not deleted");
+        }
+        let id = self.document.as_ref().and_then(|d| d.get("_id")).cloned();
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let deleted = collection
+            .find_one_and_delete(doc! { "_id": id.unwrap_or(Bson::Null) }, None)
+            .await;
+        let message = match deleted {
+            Ok(Some(deleted)) => {
+                self.show_returned("deleted document", &deleted)?;
+                "deleted the document"
+            }
+            Ok(None) => "the document was already deleted",
+            Err(e) => {
+                return ui::status(&format!("failed to delete: {}", describe_write_error(&e)))
+            }
+        };
+        self.document = None;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await?;
+        ui::status(message)
+    }
+
+    /// Moves the open document to the collection's trash, where it can be recovered from.
+    async fn trash_document(&mut self) -> Result<()> {
+        let id = self.document.as_ref().and_then(|d| d.get("_id")).cloned();
+        let database = self.database.as_ref().unwrap();
+        let trash_name = format!("{}{TRASH_SUFFIX}", self.collection_name);
+        let collection = database.collection::<Document>(&self.collection_name);
+        let trash = database.collection::<Document>(&trash_name);
+        let moved = move_to_trash(&self.client, &collection, &trash, id.unwrap_or(Bson::Null));
+        let message = match ui::cancellable(moved).await? {
+            Some(Ok(Some(_))) => format!("moved the document to {trash_name}"),
+            Some(Ok(None)) => String::from("the document was already deleted"),
+            Some(Err(e)) => {
+                return ui::status(&format!(
+                    "failed to move the document to {trash_name}: {}",
+                    describe_write_error(&e)
+                ))
+            }
+            None => return ui::status("cancelled"),
+        };
+        self.document = None;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await?;
+        ui::status(&message)
+    }
+
+    /// Shows a document returned by a write in a panel, under `title`.
+    fn show_returned(&self, title: &str, document: &Document) -> Result<()> {
+        let text = render::pretty(&self.displayed(document), self.indent)?;
+        let mut lines = vec![cformat!(
+            "<yellow>{} in {}</yellow>",
+            title,
+            self.namespace()
+        )];
+        lines.extend(text.lines().map(String::from));
+        ui::panel(&lines, &[])?;
+        Ok(())
+    }
+
+    /// Prompts for a filter and an update, counts the documents they'd change and, once the user
+    /// confirms, updates them all.
+    async fn update_many(&mut self) -> Result<()> {
+        if self.read_only {
+            return ui::status("read-only mode");
+        }
+        let paths = query::field_paths(&self.documents);
+        let filter = match ui::prompt_json(
+            "update documents matching (empty for the current filter): ",
+            query::balance,
+            &paths,
+            query::check(query::parse_document),
+        )? {
+            Some(input) if input.trim().is_empty() => self.filter.clone().unwrap_or_default(),
+            Some(input) => match query::parse_document(input.trim()) {
+                Ok(filter) => filter,
+                Err(e) => return ui::status(&format!("invalid filter: {e}")),
+            },
+            None => return Ok(()),
+        };
+        let update = match ui::prompt_json(
+            "update, e.g. {$set: {status: 'done'}} or a pipeline: ",
+            query::balance,
+            &[],
+            query::check(query::parse_update),
+        )? {
+            Some(input) if !input.trim().is_empty() => input,
+            _ => return Ok(()),
+        };
+        let update = match query::parse_update(update.trim()) {
+            Ok(update) => update,
+            Err(e) => return ui::status(&format!("invalid update: {e}")),
+        };
+
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let matching =
+            match ui::cancellable(count(&collection, Some(filter.clone()), self, true)).await? {
+                Some(Ok(matching)) => matching.count,
+                Some(Err(e)) => return ui::status(&format!("failed to count the documents: {e}")),
+                None => return ui::status("cancelled"),
+            };
+        if matching == 0 {
+            return ui::status("no documents match");
+        }
+        let confirmed = if self.strict_confirm {
+            let input = ui::prompt(&format!("type {matching} to update that many documents: "))?;
+            input.as_deref().map(str::trim) == Some(matching.to_string().as_str())
+        } else {
+            ui::confirm_within(
+                &format!("update {matching} documents?"),
+                self.confirm_timeout,
+            )?
+        };
+        if !confirmed {
+            return ui::status("not updated");
+        }
+
+        let options = UpdateOptions::builder()
+            .collation(self.collation())
+            .hint(self.hint.clone())
+            .build();
+        let message = match collection.update_many(filter, update, options).await {
+            Ok(result) => format!(
+                "matched {}, modified {}",
+                result.matched_count, result.modified_count
+            ),
+            Err(e) => format!("failed to update: {}", describe_write_error(&e)),
+        };
+        self.total = None;
+        self.redraw().await?;
+        ui::status(&message)
+    }
+
+    /// Goes back to the list of databases, forgetting the open document, collection and search.
+    async fn reset(&mut self) -> Result<()> {
+        self.reset_collection_view();
+        self.document = None;
+        self.tree = self.new_tree();
+        self.database_search = None;
+        self.change_state(&State::Default, None).await
+    }
+
+    /// Forgets the filter and other query settings of the open collection.
+    fn reset_collection_view(&mut self) {
+        self.filter = None;
+        self.hint = None;
+        self.pipeline = None;
+        self.sort_by_score = false;
+        self.natural = None;
+        self.results = None;
+        self.total = None;
+        self.page = 0;
+        self.skip = 0;
+        self.limit = None;
+    }
+
+    /// Re-renders the collection or document from the already fetched data,
+    /// keeping the cursor in place.
+    fn repaint(&self) -> Result<()> {
+        let row = cursor::position()?.1;
+        terminal::disable_raw_mode()?;
+        match self.state {
+            State::InsideDocument => self.draw_document()?,
+            _ => self.draw_collection()?,
+        }
+        execute!(io::stdout(), cursor::MoveToRow(row))?;
+        terminal::enable_raw_mode()?;
+        self.draw_footer()
+    }
+
+    /// For users who may read some collections of `db` but not list them: asks for the name
+    /// of a collection to open instead, going back to the databases if there's none.
+    async fn enter_unlisted_collection(&mut self, db: Database) -> Result<()> {
+        let input = ui::prompt("not permitted to list collections; open collection: ")?;
+        match input.as_deref().map(str::trim) {
+            Some(name) if !name.is_empty() => {
+                // The pinned collections are the only names to go by.
+                let name = matching_name(&self.pinned(Some(db.name())), name);
+                self.database = Some(db);
+                self.collection_list = None;
+                self.collection_name = name.clone();
+                self.change_state(&State::InsideCollection, Some(&name))
+                    .await
             }
+            _ => self.change_state(&State::Default, None).await,
         }
     }
+
+    /// Re-renders the current state.
+    async fn redraw(&mut self) -> Result<()> {
+        let name = match self.state {
+            State::Default => None,
+            State::InsideDatabase => Some(self.database_name.clone()),
+            State::InsideCollection => Some(self.collection_name.clone()),
+            State::InsideDocument => None,
+        };
+        self.change_state(&self.state.clone(), name.as_deref())
+            .await
+    }
+
+    fn draw_footer(&self) -> Result<()> {
+        let database = match self.state {
+            State::Default => "-",
+            _ => &self.database_name,
+        };
+        let tab = match self.tab.as_str() {
+            "" => String::new(),
+            tab => format!(" [{tab}] {} |", self.host_key()),
+        };
+        let latency = match self.latency {
+            Some(latency) => format!(" | ({} ms)", latency.as_millis()),
+            None => String::new(),
+        };
+        let mut location = self.location();
+        location.truncate(2);
+        ui::set_title(&match location.is_empty() {
+            true => String::from("termongo"),
+            false => format!("termongo — {}", location.join("/")),
+        })?;
+        let text = format!(
+            "{tab} db: {database} | w: {} | json: {} | dates: {}{latency}",
+            describe_write_concern(self.options.write_concern.as_ref()),
+            self.json,
+            self.dates.timezone
+        );
+        match self.production {
+            true => ui::footer_with_banner(" PRODUCTION ", &text),
+            false => ui::footer(&text),
+        }
+    }
+
+    async fn show_info(&mut self) -> Result<()> {
+        let hosts: Vec<String> = self.options.hosts.iter().map(|h| h.to_string()).collect();
+        let mut lines = vec![
+            cformat!("<yellow>connection</yellow>"),
+            cformat!("  <green>hosts</green>          {}", hosts.join(", ")),
+            cformat!(
+                "  <green>app name</green>       {}",
+                self.options.app_name.as_deref().unwrap_or_default()
+            ),
+            cformat!(
+                "  <green>write concern</green>  {}",
+                describe_write_concern(self.options.write_concern.as_ref())
+            ),
+            cformat!(
+                "  <green>read pref</green>      {}",
+                describe_read_preference(self.options.selection_criteria.as_ref())
+            ),
+            cformat!(
+                "  <green>read concern</green>   {}",
+                describe_read_concern(self.options.read_concern.as_ref())
+            ),
+        ];
+
+        let options = connection::describe_options(&self.options);
+        if !options.is_empty() {
+            let width = options
+                .iter()
+                .map(|(name, _)| name.len())
+                .max()
+                .unwrap_or_default();
+            lines.push(String::new());
+            lines.push(cformat!("<yellow>options</yellow>"));
+            for (name, value) in options {
+                lines.push(cformat!(
+                    "  <green>{:width$}</green>  {}",
+                    name,
+                    value,
+                    width = width
+                ));
+            }
+        }
+
+        let namespace = match self.state {
+            State::InsideCollection | State::InsideDocument => Some(self.namespace()),
+            _ => None,
+        };
+        match ui::cancellable(sharding::describe(&self.client, namespace.as_deref())).await? {
+            Some(Ok(Some(sharding))) => {
+                lines.push(String::new());
+                lines.extend(sharding);
+                // With a single router, there's nothing to balance or pin.
+                if self.options.hosts.len() > 1 {
+                    lines.push(String::new());
+                    lines.push(cformat!("<yellow>routers</yellow>"));
+                    lines.extend(self.routers.describe(&self.options.hosts));
+                    lines.push(match &self.mongos {
+                        Some(mongos) => format!("  pinned to {mongos} with --mongos"),
+                        None => String::from("  pin one with --mongos <host>"),
+                    });
+                }
+            }
+            Some(Ok(None)) | None => {}
+            Some(Err(e)) => {
+                lines.push(String::new());
+                lines.push(format!("(no sharding information: {e})"));
+            }
+        }
+        lines.push(String::new());
+        lines.push(String::from("o: tail the oplog, c: current operations"));
+
+        let shown = match ui::panel(&lines, &['o', 'c'])? {
+            Some('o') => oplog::tail(&self.client).await,
+            Some('c') => {
+                operations::browse(&self.client, self.read_only, self.confirm_timeout).await
+            }
+            _ => return self.redraw().await,
+        };
+        self.redraw().await?;
+        if let Err(e) = shown {
+            ui::status(&e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Watches the collection for changes, resuming after the last change seen last time, and
+    /// saves where it stopped.
+    async fn watch(&mut self) -> Result<()> {
+        let host = self.host_key();
+        let namespace = self.namespace();
+        let mut token = self
+            .config
+            .resume_tokens
+            .get(&host)
+            .and_then(|tokens| tokens.get(&namespace))
+            .and_then(|token| Bson::try_from(token.clone()).ok())
+            .and_then(|token| mongodb::bson::from_bson(token).ok());
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let watched = watch::watch(&collection, &mut token).await;
+
+        let token = token.and_then(|token| mongodb::bson::to_bson(&token).ok());
+        let saved = self.update_config(|config| {
+            let tokens = config.resume_tokens.entry(host).or_default();
+            match token {
+                Some(token) => tokens.insert(namespace, token.into_relaxed_extjson()),
+                None => tokens.remove(&namespace),
+            };
+        });
+        self.redraw().await?;
+        match (watched, saved) {
+            (Err(e), _) => ui::status(&e.to_string()),
+            (Ok(()), Err(e)) => ui::status(&format!("failed to save the resume token: {e}")),
+            (Ok(()), Ok(())) => Ok(()),
+        }
+    }
+
+    async fn peek(&self) -> Result<()> {
+        let index = match cursor::position()?.1.checked_sub(1) {
+            Some(index) => index as usize,
+            None => return Ok(()),
+        };
+        let name = match self
+            .collection_list
+            .as_ref()
+            .and_then(|list| list.iter().find(|(_, i)| *i == index))
+        {
+            Some((name, _)) => name,
+            None => return ui::preview(""),
+        };
+
+        let collection = self.database.as_ref().unwrap().collection::<Document>(name);
+        let options = FindOneOptions::builder().max_time(self.max_time).build();
+        match collection.find_one(None, options).await {
+            Ok(Some(document)) => ui::preview(&self.displayed(&document).to_string()),
+            Ok(None) => ui::preview("(empty collection)"),
+            Err(e) => ui::preview(&format!("(no preview: {e})")),
+        }
+    }
+
+    async fn jump_to_id(&mut self) -> Result<()> {
+        let id = match ui::prompt("_id: ")? {
+            Some(id) if !id.trim().is_empty() => parse_id(id.trim()),
+            _ => return Ok(()),
+        };
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+
+        let options = FindOneOptions::builder()
+            .max_time(self.max_time)
+            .collation(self.collation())
+            .build();
+        let query = collection.find_one(doc! { "_id": id }, options);
+        let started = Instant::now();
+        let document = match ui::cancellable(query).await? {
+            Some(Err(e)) if is_max_time_expired(&e) => return self.report_max_time_expired(),
+            Some(Err(e)) => return ui::status(&format!("failed to find the document: {e}")),
+            Some(Ok(document)) => document,
+            None => return ui::status("cancelled"),
+        };
+        self.latency = Some(started.elapsed());
+        match document {
+            Some(document) => {
+                self.document = Some(document);
+                self.tree = self.new_tree();
+                self.change_state(&State::InsideDocument, None).await
+            }
+            None => ui::status("no document with that _id"),
+        }
+    }
+
+    async fn show_indexes(&mut self) -> Result<()> {
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let browsed = indexes::browse(&collection, self.read_only, self.confirm_timeout).await;
+        self.redraw().await?;
+        if let Err(e) = browsed {
+            ui::status(&format!("indexes: {e}"))?;
+        }
+        Ok(())
+    }
+
+    fn report_max_time_expired(&self) -> Result<()> {
+        ui::status(&self.describe_max_time_expired())
+    }
+
+    fn describe_max_time_expired(&self) -> String {
+        let limit = self.max_time.unwrap_or_default().as_millis();
+        format!("query exceeded the time limit of {limit} ms")
+    }
+
+    /// What went wrong fetching the documents of the collection view, for the status line.
+    fn describe_fetch_error(&self, error: &mongodb::error::Error) -> String {
+        if is_max_time_expired(error) {
+            self.describe_max_time_expired()
+        } else if is_memory_limit_exceeded(error) {
+            String::from(
+                "the pipeline ran out of memory, allow it to use the disk with :allowdiskuse or \
+                 --allow-disk-use",
+            )
+        } else if self.pipeline.is_some() {
+            format!("aggregation failed: {error}")
+        } else {
+            format!("query failed: {error}")
+        }
+    }
+
+    /// Identifies the deployment pins are stored under.
+    fn host_key(&self) -> String {
+        let hosts: Vec<String> = self.options.hosts.iter().map(|h| h.to_string()).collect();
+        hosts.join(",")
+    }
+
+    /// The pinned databases, or the pinned collections of `database`.
+    fn pinned(&self, database: Option<&str>) -> Vec<String> {
+        let pins = self.config.pins.get(&self.host_key());
+        pins.into_iter()
+            .flatten()
+            .filter_map(|pin| match (database, pin.split_once('.')) {
+                (None, None) => Some(pin.clone()),
+                (Some(database), Some((db, collection))) if db == database => {
+                    Some(collection.to_string())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Pins the highlighted database or collection, or unpins it if it already is.
+    async fn toggle_pin(&mut self) -> Result<()> {
+        let row = cursor::position()?.1 as usize;
+        let (list, index) = match self.state {
+            State::Default => (Some(&self.list), Some(row)),
+            _ => (self.collection_list.as_ref(), row.checked_sub(1)),
+        };
+        let name = match list
+            .zip(index)
+            .and_then(|(l, i)| l.iter().find(|(_, j)| *j == i))
+        {
+            Some((name, _)) => name.clone(),
+            None => return Ok(()),
+        };
+        let pin = match self.state {
+            State::Default => name.clone(),
+            _ => format!("{}.{}", self.database_name, name),
+        };
+        // Keeps the cursor on the entry once the list is rebuilt.
+        match self.state {
+            State::Default => self.database_name = name,
+            _ => self.collection_name = name,
+        }
+
+        let host = self.host_key();
+        let saved = self.update_config(|config| {
+            let pins = config.pins.entry(host).or_default();
+            match pins.iter().position(|p| *p == pin) {
+                Some(i) => {
+                    pins.remove(i);
+                }
+                None => pins.push(pin),
+            }
+        });
+        if let Err(e) = saved {
+            return ui::status(&format!("failed to save pins: {e}"));
+        }
+        self.redraw().await
+    }
+
+    /// Applies `change` to the config file and saves it.
+    fn update_config(&mut self, change: impl FnOnce(&mut Config)) -> Result<()> {
+        // Other tabs may have changed the file since this one read it.
+        let mut config = Config::load()?;
+        change(&mut config);
+        config.save()?;
+        self.config = config;
+        Ok(())
+    }
+
+    /// Where the current view is: the database, collection and open document's `_id` it's in.
+    fn location(&self) -> Vec<String> {
+        let id = match (&self.state, &self.document) {
+            (State::InsideDocument, Some(document)) => document.get("_id"),
+            _ => None,
+        };
+        let depth = match self.state {
+            State::Default => 0,
+            State::InsideDatabase => 1,
+            _ => 2,
+        };
+        [self.database_name.clone(), self.collection_name.clone()]
+            .into_iter()
+            .take(depth)
+            .chain(id.map(|id| copyable(id, self.json)))
+            .collect()
+    }
+
+    /// Copies where the current view is to the clipboard, e.g.
+    /// `host / analytics / events (filter: {"type": "click"})`.
+    fn copy_location(&self) -> Result<()> {
+        let mut text = [self.host_key()]
+            .into_iter()
+            .chain(self.location())
+            .collect::<Vec<_>>()
+            .join(" / ");
+        if matches!(self.state, State::InsideCollection | State::InsideDocument) {
+            let mut query = Vec::new();
+            if let Some(filter) = &self.filter {
+                query.push(format!("filter: {}", self.json.to_value(filter)));
+            }
+            if let Some(pipeline) = &self.pipeline {
+                let stages: Vec<Value> = pipeline.iter().map(|s| self.json.to_value(s)).collect();
+                query.push(format!("pipeline: {}", Value::Array(stages)));
+            }
+            if let Some(hint) = &self.hint {
+                query.push(match hint {
+                    Hint::Name(name) => format!("hint: {name}"),
+                    Hint::Keys(keys) => format!("hint: {}", self.json.to_value(keys)),
+                    _ => String::from("hint"),
+                });
+            }
+            if self.skip > 0 {
+                query.push(format!("skip: {}", self.skip));
+            }
+            if let Some(limit) = self.limit {
+                query.push(format!("limit: {limit}"));
+            }
+            if !query.is_empty() {
+                text = format!("{text} ({})", query.join(", "));
+            }
+        }
+        ui::copy(&text)?;
+        ui::status(&format!("copied {text}"))
+    }
+
+    fn namespace(&self) -> String {
+        format!("{}.{}", self.database_name, self.collection_name)
+    }
+
+    /// The collation saved for the current collection.
+    fn collation(&self) -> Option<Collation> {
+        let collations = self.config.collations.get(&self.host_key())?;
+        collations.get(&self.namespace()).cloned()
+    }
+
+    /// Reads a command from the `:` prompt and runs it.
+    async fn run_command(&mut self) -> Result<()> {
+        let input = match ui::prompt(":")? {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        let (command, argument) = match input.trim().split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input.trim(), ""),
+        };
+        match command {
+            "" => Ok(()),
+            "collation" => self.set_collation(argument).await,
+            "struct" => self.copy_struct(),
+            "hint" => self.set_hint(argument).await,
+            "summary" => self.set_summary_fields(argument),
+            "depth" => match argument.parse::<usize>() {
+                Ok(depth) => {
+                    self.max_depth = depth;
+                    self.tree.set_max_depth(depth);
+                    match self.state {
+                        State::InsideCollection | State::InsideDocument => self.repaint(),
+                        _ => Ok(()),
+                    }
+                }
+                _ if argument.is_empty() => ui::status(&match self.max_depth {
+                    0 => String::from("no depth limit"),
+                    depth => format!("collapsing what's nested {depth} deep"),
+                }),
+                _ => ui::status("depth takes a number of levels, 0 for no limit"),
+            },
+            "url" => self.set_url(argument),
+            "load" => self.load_query(argument).await,
+            "ping" => self.ping().await,
+            "profile" => self.profile(argument).await,
+            "counts" => {
+                self.exact_counts = !self.exact_counts;
+                ui::status(match self.exact_counts {
+                    true => "unfiltered collections are counted exactly",
+                    false => "unfiltered collections are estimated",
+                })
+            }
+            "return" => {
+                self.return_before = match argument {
+                    "before" => true,
+                    "after" => false,
+                    _ => return ui::status("return takes before or after"),
+                };
+                ui::status(&format!("updates show the document {argument} them"))
+            }
+            "skip" | "limit" => self.set_skip_or_limit(command, argument).await,
+            "sample" => match argument.parse::<usize>() {
+                Ok(n) if n > 0 => {
+                    self.sample_size = Some(n);
+                    self.sample().await
+                }
+                _ if argument.is_empty() => self.sample().await,
+                _ => ui::status("sample takes a positive number"),
+            },
+            "natural" => self.set_natural(argument).await,
+            "allowdiskuse" => {
+                self.allow_disk_use = !self.allow_disk_use;
+                match self.state {
+                    State::InsideCollection if self.pipeline.is_some() => self.redraw().await,
+                    _ => ui::status(&format!("allowDiskUse: {}", self.allow_disk_use)),
+                }
+            }
+            _ => ui::status(&format!("unknown command: {command}")),
+        }
+    }
+
+    /// Shows the operations the profiler recorded in the current database, or sets its level
+    /// from `<level> [slowms]`.
+    async fn profile(&mut self, argument: &str) -> Result<()> {
+        let database = match (&self.state, &self.database) {
+            (State::Default, _) | (_, None) => {
+                return ui::status("open a database to see its profiler")
+            }
+            (_, Some(database)) => database.clone(),
+        };
+        if argument.is_empty() {
+            let shown = profiler::browse(&database, self.read_only, self.confirm_timeout).await;
+            self.redraw().await?;
+            if let Err(e) = shown {
+                ui::status(&e.to_string())?;
+            }
+            return Ok(());
+        }
+        if self.read_only {
+            return ui::status("read-only mode");
+        }
+        let mut words = argument.split_whitespace();
+        let level = match words.next().and_then(|level| level.parse::<i32>().ok()) {
+            Some(level @ 0..=2) => level,
+            _ => return ui::status("profile takes a level of 0, 1 or 2 and optionally slowms"),
+        };
+        let slow_ms = match words.next().map(str::parse::<i64>) {
+            Some(Ok(slow_ms)) => Some(slow_ms),
+            Some(Err(_)) => return ui::status("slowms must be a number of milliseconds"),
+            None => None,
+        };
+        let message = profiler::set_level(&database, level, slow_ms, self.confirm_timeout).await?;
+        ui::status(&message)
+    }
+
+    /// Sets the collation of the current collection from either JSON or `<locale> [strength]`,
+    /// or removes it if `argument` is empty.
+    async fn set_collation(&mut self, argument: &str) -> Result<()> {
+        if !matches!(self.state, State::InsideCollection) {
+            return ui::status("open a collection to set its collation");
+        }
+        let collation = if argument.is_empty() {
+            None
+        } else {
+            let json = if argument.starts_with('{') {
+                serde_json::from_str(argument)
+            } else {
+                let mut words = argument.split_whitespace();
+                let mut json = serde_json::json!({ "locale": words.next() });
+                if let Some(strength) = words.next() {
+                    json["strength"] = match strength.parse::<u32>() {
+                        Ok(strength) => strength.into(),
+                        Err(_) => {
+                            return ui::status(&format!("invalid collation strength: {strength}"))
+                        }
+                    };
+                }
+                Ok(json)
+            };
+            match json.and_then(serde_json::from_value::<Collation>) {
+                Ok(collation) => Some(collation),
+                Err(e) => return ui::status(&format!("invalid collation: {e}")),
+            }
+        };
+
+        let (host, namespace) = (self.host_key(), self.namespace());
+        let saved = self.update_config(|config| {
+            let collations = config.collations.entry(host).or_default();
+            match collation {
+                Some(collation) => collations.insert(namespace, collation),
+                None => collations.remove(&namespace),
+            };
+        });
+        if let Err(e) = saved {
+            return ui::status(&format!("failed to save collation: {e}"));
+        }
+        self.total = None;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// Sets the fields the summary view shows for the collection, from a comma separated list,
+    /// or goes back to the first fields of each document if `argument` is empty.
+    fn set_summary_fields(&mut self, argument: &str) -> Result<()> {
+        if !matches!(self.state, State::InsideCollection) {
+            return ui::status("open a collection to set its summary fields");
+        }
+        let fields: Vec<String> = argument
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(String::from)
+            .collect();
+        let (host, namespace) = (self.host_key(), self.namespace());
+        let saved = self.update_config(|config| {
+            let summaries = config.summaries.entry(host).or_default();
+            match fields.is_empty() {
+                true => summaries.remove(&namespace),
+                false => summaries.insert(namespace, fields),
+            };
+        });
+        if let Err(e) = saved {
+            return ui::status(&format!("failed to save the summary fields: {e}"));
+        }
+        self.render_mode = RenderMode::Summary;
+        self.repaint()
+    }
+
+    /// Sets the template of the URL `o` opens documents of the collection at, or removes it if
+    /// `argument` is empty.
+    fn set_url(&mut self, argument: &str) -> Result<()> {
+        if !matches!(self.state, State::InsideCollection | State::InsideDocument) {
+            return ui::status("open a collection to set its URL");
+        }
+        if !(argument.is_empty() || argument.contains("{id}")) {
+            return ui::status("the URL needs an {id} to put the _id of documents in");
+        }
+        let (host, namespace) = (self.host_key(), self.namespace());
+        let template = argument.to_string();
+        let saved = self.update_config(|config| {
+            let urls = config.urls.entry(host).or_default();
+            match template.is_empty() {
+                true => urls.remove(&namespace),
+                false => urls.insert(namespace, template),
+            };
+        });
+        match saved {
+            Ok(()) if argument.is_empty() => ui::status("removed the URL"),
+            Ok(()) => ui::status("o opens documents at the URL"),
+            Err(e) => ui::status(&format!("failed to save the URL: {e}")),
+        }
+    }
+
+    /// Opens the selected document at the collection's URL in the browser.
+    fn open_url(&self) -> Result<()> {
+        let template = match self
+            .config
+            .urls
+            .get(&self.host_key())
+            .and_then(|urls| urls.get(&self.namespace()))
+        {
+            Some(template) => template,
+            None => return ui::status("set a URL, e.g. :url https://admin.example.com/users/{id}"),
+        };
+        let id = match self.selected_document()?.and_then(|d| d.get("_id")) {
+            Some(id) => copyable(id, self.json),
+            None => return Ok(()),
+        };
+        let url = template.replace("{id}", &percent_encode(&id));
+        match ui::open(&url) {
+            Ok(()) => ui::status(&format!("opened {url}")),
+            Err(e) => ui::status(&format!("failed to open {url}: {e}")),
+        }
+    }
+
+    /// The `_id`s bookmarked in the collection, as canonical extended JSON.
+    fn bookmarks(&self) -> &[serde_json::Value] {
+        self.config
+            .bookmarks
+            .get(&self.host_key())
+            .and_then(|bookmarks| bookmarks.get(&self.namespace()))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    fn is_bookmarked(&self, document: &Document) -> bool {
+        match document.get("_id") {
+            Some(id) => self
+                .bookmarks()
+                .contains(&id.clone().into_canonical_extjson()),
+            None => false,
+        }
+    }
+
+    /// Bookmarks the selected document, or removes its bookmark if it already has one.
+    fn bookmark_selected(&mut self) -> Result<()> {
+        let id = match self.selected_document()?.and_then(|d| d.get("_id")) {
+            Some(id) => id.clone(),
+            None => return ui::status("select a document first"),
+        };
+        let added = match self.toggle_bookmark(&id) {
+            Ok(added) => added,
+            Err(e) => return ui::status(&format!("failed to save bookmarks: {e}")),
+        };
+        if matches!(self.state, State::InsideCollection) {
+            self.repaint()?;
+        }
+        let id = copyable(&id, self.json);
+        match added {
+            true => ui::status(&format!("bookmarked {id}, ' lists the bookmarks")),
+            false => ui::status(&format!("removed the bookmark of {id}")),
+        }
+    }
+
+    /// Bookmarks the document with `id` in the collection, or removes its bookmark. Returns
+    /// whether it was added.
+    fn toggle_bookmark(&mut self, id: &Bson) -> Result<bool> {
+        let (host, namespace) = (self.host_key(), self.namespace());
+        let bookmark = id.clone().into_canonical_extjson();
+        let mut added = false;
+        self.update_config(|config| {
+            let bookmarks = config.bookmarks.entry(host).or_default();
+            let ids = bookmarks.entry(namespace.clone()).or_default();
+            match ids.iter().position(|b| *b == bookmark) {
+                Some(i) => {
+                    ids.remove(i);
+                }
+                None => {
+                    ids.push(bookmark);
+                    added = true;
+                }
+            }
+            if ids.is_empty() {
+                bookmarks.remove(&namespace);
+            }
+        })?;
+        Ok(added)
+    }
+
+    /// Lists the bookmarked documents of the collection and opens the picked one, fetched anew.
+    async fn show_bookmarks(&mut self) -> Result<()> {
+        let ids: Vec<Bson> = self
+            .bookmarks()
+            .iter()
+            .filter_map(|id| Bson::try_from(id.clone()).ok())
+            .collect();
+        if ids.is_empty() {
+            return ui::status("no bookmarks, m bookmarks the selected document");
+        }
+        let items: Vec<String> = ids.iter().map(|id| copyable(id, self.json)).collect();
+        let title = cformat!("<yellow>bookmarks in {}</yellow>", self.namespace());
+        let id = match ui::choose(&title, &items)? {
+            Some(i) => ids[i].clone(),
+            None => return self.redraw().await,
+        };
+
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let options = FindOneOptions::builder().max_time(self.max_time).build();
+        let query = collection.find_one(doc! { "_id": id.clone() }, options);
+        let found = match ui::cancellable(query).await? {
+            Some(Err(e)) if is_max_time_expired(&e) => {
+                self.redraw().await?;
+                return self.report_max_time_expired();
+            }
+            Some(found) => found?,
+            None => {
+                self.redraw().await?;
+                return ui::status("cancelled");
+            }
+        };
+        match found {
+            Some(found) => {
+                self.document = Some(found);
+                self.tree = self.new_tree();
+                self.change_state(&State::InsideDocument, None).await
+            }
+            None => {
+                self.redraw().await?;
+                let shown = copyable(&id, self.json);
+                let question = format!(
+                    "the document with _id {shown} was deleted since, remove its bookmark?"
+                );
+                if !ui::confirm(&question)? {
+                    return ui::status(&format!("the document with _id {shown} was deleted"));
+                }
+                match self.toggle_bookmark(&id) {
+                    Ok(_) => self.repaint()?,
+                    Err(e) => return ui::status(&format!("failed to save bookmarks: {e}")),
+                }
+                ui::status(&format!("removed the bookmark of {shown}"))
+            }
+        }
+    }
+
+    /// Makes the collection's queries use the index with the given name or keys, if there is one,
+    /// or lets the server pick again if `argument` is empty.
+    async fn set_hint(&mut self, argument: &str) -> Result<()> {
+        if !matches!(self.state, State::InsideCollection) {
+            return ui::status("open a collection to hint an index");
+        }
+        let hint = match argument {
+            "" => None,
+            keys if keys.starts_with('{') => match query::parse_document(keys) {
+                Ok(keys) => Some(Hint::Keys(keys)),
+                Err(e) => return ui::status(&format!("invalid index keys: {e}")),
+            },
+            name => Some(Hint::Name(name.to_string())),
+        };
+
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let indexes = match indexes::list(&collection).await {
+            Ok(indexes) => indexes,
+            Err(e) => return ui::status(&format!("failed to list indexes: {e}")),
+        };
+        let exists = match &hint {
+            Some(Hint::Name(name)) => indexes
+                .iter()
+                .any(|index| index.options.as_ref().and_then(|o| o.name.as_ref()) == Some(name)),
+            Some(Hint::Keys(keys)) => indexes.iter().any(|index| index.keys == *keys),
+            _ => true,
+        };
+        // The server fails queries hinting at an index that isn't there.
+        if !exists {
+            return ui::status("no index matches the hint");
+        }
+
+        self.hint = hint;
+        self.total = None;
+        self.redraw().await
+    }
+
+    /// Sorts the collection's finds in insertion order (`1`) or the reverse (`-1`), or by
+    /// whatever the server picks again (`off`). Without an argument, goes from off to 1 to -1.
+    async fn set_natural(&mut self, argument: &str) -> Result<()> {
+        if !matches!(self.state, State::InsideCollection) {
+            return ui::status("open a collection to sort it in insertion order");
+        }
+        self.natural = match (argument, self.natural) {
+            ("", None) => Some(1),
+            ("", Some(1)) => Some(-1),
+            ("", Some(_)) | ("off", _) => None,
+            ("1", _) => Some(1),
+            ("-1", _) => Some(-1),
+            _ => return ui::status("expected 1, -1 or off"),
+        };
+        self.page = 0;
+        self.redraw().await?;
+        match self.natural {
+            Some(_) if self.pipeline.is_some() => {
+                ui::status("$natural only sorts finds, not pipelines")
+            }
+            Some(_) if self.sort_by_score => {
+                ui::status("$natural is ignored when sorting by relevance")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn page_limit(&self) -> usize {
+        self.limit.unwrap_or(self.page_size)
+    }
+
+    /// Position in the results of the first document of the page.
+    fn first_index(&self) -> usize {
+        self.skip + self.page * self.page_limit()
+    }
+
+    /// Sets the `:skip` or `:limit` of the collection, or removes it if `argument` is empty.
+    async fn set_skip_or_limit(&mut self, command: &str, argument: &str) -> Result<()> {
+        if !matches!(self.state, State::InsideCollection) {
+            return ui::status(&format!("open a collection to set its {command}"));
+        }
+        let value = match argument {
+            "" => None,
+            n => match n.parse::<usize>() {
+                Ok(n) if command == "skip" || n > 0 => Some(n),
+                _ => return ui::status(&format!("{command} takes a positive number")),
+            },
+        };
+        match command {
+            "skip" => self.skip = value.unwrap_or_default(),
+            _ => self.limit = value,
+        }
+        self.page = 0;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    async fn change_page(&mut self, delta: isize) -> Result<()> {
+        let page = match self.page.checked_add_signed(delta) {
+            Some(page) => page,
+            None => return Ok(()),
+        };
+        if delta > 0 && !self.has_more {
+            return ui::status("(last page)");
+        }
+        self.page = page;
+        self.paging_forward = delta > 0;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    async fn set_max_time(&mut self) -> Result<()> {
+        let input = match ui::prompt("max time in ms (empty for none): ")? {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        self.max_time = match input.trim() {
+            "" => None,
+            ms => match ms.parse() {
+                Ok(ms) => Some(Duration::from_millis(ms)),
+                Err(_) => return ui::status("not a number of milliseconds"),
+            },
+        };
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// The document open in the document view, or highlighted in the collection view.
+    fn selected_document(&self) -> Result<Option<&Document>> {
+        Ok(match self.state {
+            State::InsideDocument => self.document.as_ref(),
+            State::InsideCollection
+                if !matches!(
+                    self.render_mode,
+                    RenderMode::Pretty | RenderMode::Yaml | RenderMode::Flat
+                ) =>
+            {
+                let header = match self.render_mode {
+                    RenderMode::Table if table::fits(&self.documents) => 1 + table::HEADER_LINES,
+                    _ => 1,
+                };
+                let index = (cursor::position()?.1 as usize).checked_sub(header);
+                index.and_then(|i| self.documents.get(i))
+            }
+            _ => None,
+        })
+    }
+
+    /// Copies serde structs matching the selected document to the clipboard.
+    fn copy_struct(&self) -> Result<()> {
+        let document = match self.selected_document()? {
+            Some(document) => document,
+            None => return ui::status("select a document first"),
+        };
+        ui::copy(&codegen::rust_struct(&self.collection_name, document))?;
+        ui::status("copied the struct to the clipboard")
+    }
+
+    /// Asks whether to open collection `name` anyway if it's estimated to hold more than
+    /// `large_collection` documents. Collections filtered by a database search, and views,
+    /// which can't be estimated, are opened without asking.
+    async fn confirm_open(&self, database: &Database, name: &str) -> Result<bool> {
+        if self.large_collection == 0 || self.database_search.is_some() {
+            return Ok(true);
+        }
+        let collection = database.collection::<Document>(name);
+        match ui::cancellable(collection.estimated_document_count(None)).await? {
+            Some(Ok(count)) if count > self.large_collection => {
+                ui::confirm(&format!("large collection ({count} docs), open anyway?"))
+            }
+            Some(_) => Ok(true),
+            None => Ok(false),
+        }
+    }
+
+    /// Drops the highlighted collection once the user confirms it.
+    async fn drop_collection(&mut self) -> Result<()> {
+        if self.read_only {
+            return ui::status("read-only mode");
+        }
+        let index = (cursor::position()?.1 as usize).checked_sub(1);
+        let name = match self
+            .collection_list
+            .as_ref()
+            .zip(index)
+            .and_then(|(list, i)| list.iter().find(|(_, j)| *j == i))
+        {
+            Some((name, _)) => name.clone(),
+            None => return Ok(()),
+        };
+
+        let confirmed = if self.strict_confirm {
+            let input = ui::prompt(&format!("type {name} to drop it: "))?;
+            input.as_deref().map(str::trim) == Some(name.as_str())
+        } else {
+            ui::confirm_within(&format!("drop collection {name}?"), self.confirm_timeout)?
+        };
+        if !confirmed {
+            return ui::status("not dropped");
+        }
+
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&name);
+        if let Err(e) = collection.drop(None).await {
+            return ui::status(&format!(
+                "failed to drop {name}: {}",
+                describe_write_error(&e)
+            ));
+        }
+        self.redraw().await?;
+        ui::status(&format!("dropped {name}"))
+    }
+
+    /// Counts the documents matching a filter in every listed collection, then lists only the
+    /// collections that had matches. Searching again narrows down the previous matches.
+    async fn search_database(&mut self) -> Result<()> {
+        let input = match ui::prompt_json(
+            "search all collections for: ",
+            query::balance,
+            &[],
+            query::check(query::parse_document),
+        )? {
+            Some(input) if !input.trim().is_empty() => input,
+            _ => return Ok(()),
+        };
+        let filter = match query::parse_document(input.trim()) {
+            Ok(filter) => filter,
+            Err(e) => return ui::status(&format!("invalid filter: {e}")),
+        };
+
+        let database = self.database.clone().unwrap();
+        let mut names: Vec<String> = self
+            .collection_list
+            .iter()
+            .flatten()
+            .map(|(name, _)| name.clone())
+            .collect();
+        // Pinned collections are listed twice.
+        names.sort();
+        names.dedup();
+
+        let counts = stream::iter(names)
+            .map(|name| {
+                let collection = database.collection::<Document>(&name);
+                let options = CountOptions::builder().max_time(self.max_time).build();
+                let filter = filter.clone();
+                async move { (name, collection.count_documents(filter, options).await) }
+            })
+            .buffer_unordered(SEARCH_CONCURRENCY)
+            .collect::<Vec<_>>();
+        let counts = match ui::cancellable(counts).await? {
+            Some(counts) => counts,
+            None => return ui::status("cancelled"),
+        };
+
+        let failed = counts.iter().filter(|(_, count)| count.is_err()).count();
+        let matches = counts
+            .into_iter()
+            .filter_map(|(name, count)| count.ok().filter(|&n| n > 0).map(|n| (name, n)))
+            .collect();
+        self.database_search = Some((filter, matches));
+        self.redraw().await?;
+        if failed > 0 {
+            ui::status(&format!("{failed} collections couldn't be searched"))?;
+        }
+        Ok(())
+    }
+
+    /// Prompts for a pipeline to run on the documents matching the current filter.
+    /// An empty pipeline goes back to plain documents.
+    async fn aggregate(&mut self) -> Result<()> {
+        let input = match ui::prompt_json(
+            "pipeline, e.g. [{$group: {_id: \"$status\"}}]: ",
+            query::balance,
+            &[],
+            query::check(query::parse_pipeline),
+        )? {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        self.pipeline = match input.trim() {
+            "" => None,
+            input => match query::parse_pipeline(input) {
+                Ok(pipeline) if output_of(&pipeline).is_some() => {
+                    return self.run_output_pipeline(pipeline).await
+                }
+                Ok(pipeline) => Some(pipeline),
+                Err(e) => return ui::status(&format!("invalid pipeline: {e}")),
+            },
+        };
+        self.total = None;
+        self.page = 0;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// Shows random documents matching the filter with `$sample`, different ones on every call.
+    async fn sample(&mut self) -> Result<()> {
+        if !matches!(self.state, State::InsideCollection) {
+            return ui::status("open a collection to sample it");
+        }
+        let size = self.sample_size.unwrap_or(self.page_limit()) as i64;
+        self.pipeline = Some(vec![doc! { "$sample": { "size": size } }]);
+        self.total = None;
+        self.page = 0;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// Runs the last query of the collection view again, from wherever the user is now,
+    /// with a fresh count.
+    async fn rerun_last_query(&mut self) -> Result<()> {
+        let query = match self.last_query.clone() {
+            Some(query) => query,
+            None => return ui::status("no query to run again yet"),
+        };
+        if self.database_name != query.database_name {
+            self.database = Some(self.client.database(&query.database_name));
+            self.database_search = None;
+        }
+        self.set_query(query);
+        self.total = None;
+        self.document = None;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// Lets the user pick another collection, in any database, from a sidebar over the
+    /// collection view, and opens it.
+    async fn open_sidebar(&mut self) -> Result<()> {
+        let row = cursor::position()?.1;
+        let current = (self.database_name.as_str(), self.collection_name.as_str());
+        let picked = sidebar::pick(&self.client, &self.database_names, current).await?;
+        // The sidebar moved the cursor, which is the selection of the documents.
+        execute!(io::stdout(), cursor::MoveToRow(row))?;
+        let (database_name, collection_name) = match picked {
+            Some(picked)
+                if picked != (self.database_name.clone(), self.collection_name.clone()) =>
+            {
+                picked
+            }
+            _ => {
+                self.repaint()?;
+                return ui::status("");
+            }
+        };
+        self.open_namespace(database_name, collection_name).await
+    }
+
+    /// Opens a collection from any view, with a fresh filter and page. The names may be typed
+    /// in another case than the database and collection have, see `matching_name`.
+    async fn open_namespace(
+        &mut self,
+        database_name: String,
+        collection_name: String,
+    ) -> Result<()> {
+        let database_name = matching_name(&self.database_names, &database_name);
+        let names = self
+            .client
+            .database(&database_name)
+            .list_collection_names(None)
+            .await
+            .unwrap_or_default();
+        let collection_name = matching_name(&names, &collection_name);
+        if database_name != self.database_name {
+            self.database = Some(self.client.database(&database_name));
+            self.database_search = None;
+        }
+        self.database_name = database_name;
+        self.collection_name = collection_name;
+        self.reset_collection_view();
+        self.document = None;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// Lists the collections, in every database, whose names match a pattern, and opens the one
+    /// picked.
+    async fn find_collections(&mut self) -> Result<()> {
+        let pattern = match ui::prompt("collection name pattern (a regex, any case): ")? {
+            Some(pattern) if !pattern.trim().is_empty() => pattern.trim().to_string(),
+            _ => return Ok(()),
+        };
+        let client = self.client.clone();
+        let query = async {
+            let names = client.list_database_names(None, None).await?;
+            let filter = doc! { "name": { "$regex": &pattern, "$options": "i" } };
+            let listed = stream::iter(names)
+                .map(|name| {
+                    let database = client.database(&name);
+                    let filter = filter.clone();
+                    async move { (name, database.list_collection_names(filter).await) }
+                })
+                .buffer_unordered(SEARCH_CONCURRENCY)
+                .collect::<Vec<_>>()
+                .await;
+            Ok::<_, mongodb::error::Error>(listed)
+        };
+        let listed = match ui::cancellable(query).await? {
+            Some(Ok(listed)) => listed,
+            Some(Err(e)) => return ui::status(&format!("failed to list the databases: {e}")),
+            None => return ui::status("cancelled"),
+        };
+
+        let failed = listed.iter().filter(|(_, names)| names.is_err()).count();
+        let mut found: Vec<(String, String)> = listed
+            .into_iter()
+            .filter_map(|(database, names)| names.ok().map(|names| (database, names)))
+            .flat_map(|(database, names)| {
+                names
+                    .into_iter()
+                    .filter(|name| !name.starts_with("system.buckets."))
+                    .map(move |name| (database.clone(), name))
+            })
+            .collect();
+        if found.is_empty() {
+            return ui::status(&match failed {
+                0 => format!("no collection matches {pattern}"),
+                _ => format!(
+                    "no collection matches {pattern} ({failed} databases couldn't be listed)"
+                ),
+            });
+        }
+        found.sort();
+
+        let items: Vec<String> = found
+            .iter()
+            .map(|(database, name)| cformat!("<yellow>{}</yellow>.{}", database, name))
+            .collect();
+        let title = match failed {
+            0 => cformat!("<yellow>collections matching {}</yellow>", pattern),
+            _ => cformat!(
+                "<yellow>collections matching {}</yellow> <cyan>({} databases couldn't be listed)</cyan>",
+                pattern,
+                failed
+            ),
+        };
+        match ui::choose(&title, &items)? {
+            Some(i) => {
+                let (database_name, collection_name) = found.swap_remove(i);
+                let database = self.client.database(&database_name);
+                if !self.confirm_open(&database, &collection_name).await? {
+                    return self.redraw().await;
+                }
+                self.open_namespace(database_name, collection_name).await
+            }
+            None => self.redraw().await,
+        }
+    }
+
+    /// Marks the selected document, or compares it to the one marked before, possibly in another
+    /// collection, side by side.
+    fn mark_for_comparison(&mut self) -> Result<()> {
+        let document = match self.selected_document()? {
+            Some(document) => document.clone(),
+            None => return ui::status("select a document first"),
+        };
+        let (namespace, marked) = match self.marked.take() {
+            Some((namespace, marked)) if namespace == self.namespace() && marked == document => {
+                return ui::status("unmarked the document")
+            }
+            Some(marked) => marked,
+            None => {
+                self.marked = Some((self.namespace(), document));
+                return ui::status("marked the document, space on another one to compare them");
+            }
+        };
+
+        let describe = |namespace: &str, document: &Document| match document.get("_id") {
+            Some(id) => format!("{namespace} {}", copyable(id, self.json)),
+            None => namespace.to_string(),
+        };
+        let (columns, _) = terminal::size()?;
+        let mut lines = vec![cformat!(
+            "<yellow>{}</yellow> vs <yellow>{}</yellow>",
+            describe(&namespace, &marked),
+            describe(&self.namespace(), &document)
+        )];
+        lines.extend(diff::side_by_side(
+            &marked,
+            &document,
+            self.json,
+            columns as usize,
+        ));
+        ui::panel(&lines, &[])?;
+        self.repaint()
+    }
+
+    /// Replaces an estimated total with an exact count.
+    async fn count_exactly(&mut self) -> Result<()> {
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let query = count(&collection, self.filter.clone(), self, true);
+        self.total = match ui::cancellable_as("counting…", query).await? {
+            Some(Ok(total)) => Some(total),
+            Some(Err(e)) => return ui::status(&format!("failed to count: {e}")),
+            None => return ui::status("cancelled"),
+        };
+        self.repaint()
+    }
+
+    /// Shows the round trip times of a few pings in a panel.
+    async fn ping(&mut self) -> Result<()> {
+        let round_trips = match ui::cancellable(connection::ping(&self.client)).await? {
+            Some(Ok(round_trips)) => round_trips,
+            Some(Err(e)) => return ui::status(&format!("ping failed: {e}")),
+            None => return ui::status("cancelled"),
+        };
+        let mut lines = vec![cformat!("<yellow>ping</yellow> {}", self.host_key())];
+        for (i, round_trip) in round_trips.iter().enumerate() {
+            lines.push(format!(
+                "  {}: {:.1} ms",
+                i + 1,
+                round_trip.as_secs_f64() * 1000.0
+            ));
+        }
+        lines.push(String::new());
+        lines.push(connection::describe_round_trips(&round_trips));
+        ui::panel(&lines, &[])?;
+        self.redraw().await
+    }
+
+    /// Reads a filter, or a pipeline if it's an array, from the file at `path` and applies it
+    /// to the open collection.
+    async fn load_query(&mut self, path: &str) -> Result<()> {
+        if !matches!(self.state, State::InsideCollection) {
+            return ui::status("open a collection to load a query into");
+        }
+        if path.is_empty() {
+            return ui::status("load takes the path of a file");
+        }
+        let input = match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(e) => return ui::status(&format!("failed to read {path}: {e}")),
+        };
+        if input.trim_start().starts_with('[') {
+            match query::parse_pipeline(input.trim()) {
+                Ok(pipeline) if output_of(&pipeline).is_some() => {
+                    return self.run_output_pipeline(pipeline).await
+                }
+                Ok(pipeline) => self.pipeline = Some(pipeline),
+                Err(e) => return ui::status(&format!("invalid pipeline in {path}: {e}")),
+            }
+        } else {
+            match query::parse_document(input.trim()) {
+                Ok(filter) => self.filter = Some(filter),
+                Err(e) => return ui::status(&format!("invalid filter in {path}: {e}")),
+            }
+            self.sort_by_score = false;
+        }
+        self.total = None;
+        self.page = 0;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// Runs a pipeline ending in `$out` or `$merge`, which writes its results to a collection
+    /// rather than returning them, then offers to open that collection.
+    async fn run_output_pipeline(&mut self, pipeline: Vec<Document>) -> Result<()> {
+        if self.read_only {
+            return ui::status("read-only mode");
+        }
+        let (database_name, collection_name) = match output_of(&pipeline) {
+            Some((database, collection)) => (
+                database.unwrap_or_else(|| self.database_name.clone()),
+                collection,
+            ),
+            None => return Ok(()),
+        };
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let stages: Vec<Document> = self
+            .filter
+            .iter()
+            .map(|filter| doc! { "$match": filter })
+            .chain(pipeline)
+            .collect();
+        let options = AggregateOptions::builder()
+            .allow_disk_use(self.allow_disk_use)
+            .max_time(self.max_time)
+            .collation(self.collation())
+            .hint(self.hint.clone())
+            .build();
+        let query = async {
+            let cursor = collection.aggregate(stages, options).await?;
+            cursor.try_collect::<Vec<_>>().await
+        };
+        match ui::cancellable(query).await? {
+            Some(Ok(_)) => {}
+            Some(Err(e)) => {
+                return ui::status(&format!("aggregation failed: {}", describe_write_error(&e)))
+            }
+            None => return ui::status("cancelled"),
+        }
+
+        let target = format!("{database_name}.{collection_name}");
+        if !ui::confirm(&format!("wrote results to {target}, open it?"))? {
+            return ui::status(&format!("wrote results to {target}"));
+        }
+        if database_name != self.database_name {
+            self.database = Some(self.client.database(&database_name));
+            self.database_name = database_name;
+            self.database_search = None;
+        }
+        self.collection_name = collection_name;
+        self.reset_collection_view();
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// Prompts for a filter in shell syntax, e.g. `{name: /^a/i}`. An empty one removes it.
+    async fn set_filter(&mut self) -> Result<()> {
+        let paths = query::field_paths(&self.documents);
+        let input = match ui::prompt_json(
+            "filter: ",
+            query::balance,
+            &paths,
+            query::check(query::parse_document),
+        )? {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        self.filter = match input.trim() {
+            "" => None,
+            input => match query::parse_document(input) {
+                Ok(filter) => Some(filter),
+                Err(e) => return ui::status(&format!("invalid filter: {e}")),
+            },
+        };
+        self.sort_by_score = false;
+        self.total = None;
+        self.page = 0;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    /// Prompts for a field, lists its distinct values among the documents matching the filter,
+    /// most common first, and narrows the filter down to the one picked.
+    async fn group_by(&mut self) -> Result<()> {
+        let paths = query::field_paths(&self.documents);
+        let field = match ui::prompt_with_completion("group by field: ", |_| String::new(), &paths)?
+        {
+            Some(field) if !field.trim().is_empty() => field.trim().to_string(),
+            _ => return Ok(()),
+        };
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+        let stages: Vec<Document> = self
+            .filter
+            .iter()
+            .map(|filter| doc! { "$match": filter })
+            .chain([
+                doc! { "$group": { "_id": format!("${field}"), "count": { "$sum": 1 } } },
+                doc! { "$sort": { "count": -1, "_id": 1 } },
+                doc! { "$limit": MAX_GROUPS as i64 },
+            ])
+            .collect();
+        let options = AggregateOptions::builder()
+            .allow_disk_use(self.allow_disk_use)
+            .max_time(self.max_time)
+            .collation(self.collation())
+            .build();
+        let query = async {
+            let cursor = collection.aggregate(stages, options).await?;
+            cursor.try_collect::<Vec<_>>().await
+        };
+        let groups = match ui::cancellable(query).await? {
+            Some(Ok(groups)) => groups,
+            Some(Err(e)) => return ui::status(&format!("failed to group: {e}")),
+            None => return ui::status("cancelled"),
+        };
+
+        let values: Vec<Bson> = groups
+            .iter()
+            .map(|group| group.get("_id").cloned().unwrap_or(Bson::Null))
+            .collect();
+        let items: Vec<String> = groups
+            .iter()
+            .zip(&values)
+            .map(|(group, value)| {
+                let count = group.get("count").map(Bson::to_string).unwrap_or_default();
+                cformat!(
+                    "<green>{:>10}</green>  {}",
+                    count,
+                    copyable(value, self.json)
+                )
+            })
+            .collect();
+        let title = match groups.len() {
+            MAX_GROUPS => cformat!(
+                "<yellow>{} by {}</yellow> <cyan>(the {} most common values)</cyan>",
+                self.namespace(),
+                field,
+                MAX_GROUPS
+            ),
+            _ => cformat!("<yellow>{} by {}</yellow>", self.namespace(), field),
+        };
+        let picked = ui::choose(&title, &items)?;
+        let value = match picked.and_then(|i| values.get(i)) {
+            Some(value) => value.clone(),
+            None => return self.redraw().await,
+        };
+
+        self.narrow_filter(field, value).await
+    }
+
+    /// Shows the documents of the collection with the same value as the open document in the
+    /// selected field.
+    async fn filter_by_selected_field(&mut self) -> Result<()> {
+        if self.pipeline.is_some() {
+            return ui::status("the fields of pipeline results may not be in the collection");
+        }
+        let document = self.document.as_ref().expect("No document.");
+        let lines = self.tree.render(&self.shown_document(), self.indent);
+        let index = (cursor::position()?.1 as usize).checked_sub(1);
+        let path = match index.and_then(|i| lines.get(i)) {
+            Some(line) if !line.path.is_empty() => line.path.clone(),
+            _ => return ui::status("select a field to filter by"),
+        };
+        let value = match value_at(document, &path) {
+            Some(value) => value,
+            None => return Ok(()),
+        };
+        let field = filter_path(document, &path);
+        self.document = None;
+        self.narrow_filter(field, value).await
+    }
+
+    /// Shows the documents of the collection where `field` is `value`, among the ones the
+    /// filter already matches.
+    async fn narrow_filter(&mut self, field: String, value: Bson) -> Result<()> {
+        self.filter = Some(match self.filter.take() {
+            Some(filter) if !filter.contains_key(&field) => {
+                let mut filter = filter;
+                filter.insert(field, value);
+                filter
+            }
+            Some(filter) => doc! { "$and": [filter, { field: value }] },
+            None => doc! { field: value },
+        });
+        self.sort_by_score = false;
+        self.total = None;
+        self.page = 0;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+
+    async fn text_search(&mut self) -> Result<()> {
+        let collection = self
+            .database
+            .as_ref()
+            .unwrap()
+            .collection::<Document>(&self.collection_name);
+
+        let indexes = match indexes::list(&collection).await {
+            Ok(indexes) => indexes,
+            Err(e) => return ui::status(&format!("failed to list indexes: {e}")),
+        };
+        let has_text_index = indexes
+            .iter()
+            .any(|index| index.keys.values().any(|v| v.as_str() == Some("text")));
+        if !has_text_index {
+            return ui::status("(no text index)");
+        }
+
+        let terms = match ui::prompt("search: ")? {
+            Some(terms) if !terms.is_empty() => terms,
+            _ => return Ok(()),
+        };
+        self.sort_by_score = ui::confirm("sort by relevance?")?;
+        self.filter = Some(doc! { "$text": { "$search": terms } });
+        self.total = None;
+        self.page = 0;
+        self.change_state(
+            &State::InsideCollection,
+            Some(&self.collection_name.clone()),
+        )
+        .await
+    }
+}
+
+/// Guesses the collection a reference field points into: `userId` and `user_id` → `users`,
+/// `category` → `categories`.
+fn referenced_collection(field: &str) -> String {
+    let name = ["_id", "Id", "ID", "_ids", "Ids", "IDs"]
+        .iter()
+        .find_map(|suffix| field.strip_suffix(suffix).filter(|name| !name.is_empty()))
+        .unwrap_or(field);
+    match name.strip_suffix('y') {
+        Some(stem) if !stem.ends_with(['a', 'e', 'o', 'u']) => format!("{stem}ies"),
+        _ if name.ends_with('s') => name.to_string(),
+        _ => format!("{name}s"),
+    }
+}
+
+/// The value at a dotted path like `tags.0`, the whole document for an empty path.
+/// Paths into the wrappers of canonical extended JSON, e.g. `age.$numberInt`, stop at the
+/// wrapped value.
+fn value_at(document: &Document, path: &str) -> Option<Bson> {
+    let mut value = Bson::Document(document.clone());
+    for key in path.split('.').filter(|key| !key.is_empty()) {
+        value = match value {
+            Bson::Document(mut document) => document.remove(key)?,
+            Bson::Array(mut items) => {
+                let i: usize = key.parse().ok()?;
+                (i < items.len()).then(|| items.swap_remove(i))?
+            }
+            scalar => return Some(scalar),
+        };
+    }
+    Some(value)
+}
+
+/// The dot notation path of the field at `path` in `document` for filters, leaving array
+/// indexes out to match any item, as well as the wrappers of canonical extended JSON.
+fn filter_path(document: &Document, path: &str) -> String {
+    let mut keys = Vec::new();
+    let mut value = Bson::Document(document.clone());
+    for key in path.split('.').filter(|key| !key.is_empty()) {
+        value = match value {
+            Bson::Document(mut document) => match document.remove(key) {
+                Some(value) => {
+                    keys.push(key);
+                    value
+                }
+                None => break,
+            },
+            Bson::Array(mut items) => match key.parse::<usize>() {
+                Ok(i) if i < items.len() => items.swap_remove(i),
+                _ => break,
+            },
+            _ => break,
+        };
+    }
+    keys.join(".")
+}
+
+/// `value` the way it would be pasted elsewhere: strings without quotes, ObjectIds as hex,
+/// dates as RFC 3339, and documents and arrays as extended JSON.
+fn copyable(value: &Bson, json: Json) -> String {
+    match value {
+        Bson::String(s) => s.clone(),
+        Bson::ObjectId(oid) => oid.to_hex(),
+        Bson::DateTime(date) => date
+            .try_to_rfc3339_string()
+            .unwrap_or_else(|_| date.to_string()),
+        Bson::Double(n) => n.to_string(),
+        Bson::Int32(n) => n.to_string(),
+        Bson::Int64(n) => n.to_string(),
+        Bson::Decimal128(n) => n.to_string(),
+        Bson::Timestamp(ts) => format!("Timestamp({}, {})", ts.time, ts.increment),
+        Bson::Boolean(b) => b.to_string(),
+        Bson::Null => String::from("null"),
+        Bson::Document(document) => json.to_value(document).to_string(),
+        other => {
+            let value = json.to_value(&doc! { "value": other.clone() });
+            value["value"].to_string()
+        }
+    }
+}
+
+/// `input` as a value of the same type as `current`, typed the way `copyable` shows it, or in
+/// the syntax of queries for other types.
+fn typed_value(current: &Bson, input: &str) -> Result<Bson, String> {
+    let input = input.trim();
+    let number = |e: std::num::ParseFloatError| format!("{input} isn't a number ({e})");
+    Ok(match current {
+        Bson::String(_) => Bson::String(input.to_string()),
+        Bson::Int32(_) => match (input.parse::<i32>(), input.parse::<i64>()) {
+            (Ok(n), _) => Bson::Int32(n),
+            (_, Ok(n)) => Bson::Int64(n),
+            _ => Bson::Double(input.parse().map_err(number)?),
+        },
+        Bson::Int64(_) => match input.parse::<i64>() {
+            Ok(n) => Bson::Int64(n),
+            Err(_) => Bson::Double(input.parse().map_err(number)?),
+        },
+        Bson::Double(_) => Bson::Double(input.parse().map_err(number)?),
+        Bson::Decimal128(_) => {
+            input.parse::<f64>().map_err(number)?;
+            query::parse_value(&format!("NumberDecimal(\"{input}\")"))?
+        }
+        Bson::Boolean(_) => match input {
+            "true" => Bson::Boolean(true),
+            "false" => Bson::Boolean(false),
+            _ => return Err(String::from("expected true or false")),
+        },
+        Bson::ObjectId(_) => match ObjectId::parse_str(input) {
+            Ok(oid) => Bson::ObjectId(oid),
+            Err(_) => return Err(String::from("expected 24 hexadecimal digits")),
+        },
+        Bson::DateTime(_) => match mongodb::bson::DateTime::parse_rfc3339_str(input) {
+            Ok(date) => Bson::DateTime(date),
+            Err(_) => return Err(String::from("expected an RFC 3339 date")),
+        },
+        _ => query::parse_value(input)?,
+    })
+}
+
+/// `text` with everything but letters, digits and `-._~` percent-encoded, to go in a URL.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// The database, if it's another one, and collection a pipeline ending in `$out` or `$merge`
+/// writes to.
+fn output_of(pipeline: &[Document]) -> Option<(Option<String>, String)> {
+    let stage = pipeline.last()?;
+    let target = match stage.get("$merge") {
+        // `$merge` takes either the collection or `{into: <collection>, …}`.
+        Some(Bson::Document(merge)) => merge.get("into")?,
+        Some(target) => target,
+        None => stage.get("$out")?,
+    };
+    match target {
+        Bson::String(collection) => Some((None, collection.clone())),
+        Bson::Document(target) => Some((
+            target.get_str("db").ok().map(String::from),
+            target.get_str("coll").ok()?.to_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// Interprets a pasted `_id` as an ObjectId, a number or a string, in that order.
+/// `ObjectId("…")` and quoted strings are accepted as well.
+fn parse_id(input: &str) -> Bson {
+    let unwrapped = input
+        .strip_prefix("ObjectId(")
+        .and_then(|id| id.strip_suffix(')'))
+        .unwrap_or(input);
+    let unquoted = unwrapped
+        .strip_prefix('"')
+        .and_then(|id| id.strip_suffix('"'))
+        .or_else(|| {
+            unwrapped
+                .strip_prefix('\'')
+                .and_then(|id| id.strip_suffix('\''))
+        });
+
+    if let Ok(oid) = ObjectId::parse_str(unquoted.unwrap_or(unwrapped)) {
+        return Bson::ObjectId(oid);
+    }
+    if let Some(string) = unquoted {
+        return Bson::String(string.to_string());
+    }
+    if let Ok(number) = input.parse::<i64>() {
+        return Bson::Int64(number);
+    }
+    if let Ok(number) = input.parse::<f64>() {
+        return Bson::Double(number);
+    }
+    Bson::String(input.to_string())
+}
+
+/// Whether an aggregation failed for needing more memory than the server allows
+/// without `allowDiskUse`.
+fn is_memory_limit_exceeded(error: &mongodb::error::Error) -> bool {
+    matches!(*error.kind, ErrorKind::Command(ref command) if [292, 16819, 16945].contains(&command.code))
+}
+
+/// Whether a cursor was gone when its next batch was asked for, e.g. because it sat idle past
+/// the server's cursor timeout.
+fn is_cursor_not_found(error: &mongodb::error::Error) -> bool {
+    matches!(*error.kind, ErrorKind::Command(ref command) if command.code == 43)
+}
+
+/// Whether an operation failed because the primary stepped down or the server it was sent to
+/// went away, which lasts until the driver has found the new primary.
+fn is_failover(error: &mongodb::error::Error) -> bool {
+    // NotWritablePrimary, NotPrimaryNoSecondaryOk, NotPrimaryOrSecondary,
+    // InterruptedAtShutdown, InterruptedDueToReplStateChange, PrimarySteppedDown and
+    // ShutdownInProgress.
+    const CODES: [i32; 7] = [10107, 13435, 13436, 11600, 11602, 189, 91];
+    match *error.kind {
+        ErrorKind::Io(_)
+        | ErrorKind::ConnectionPoolCleared { .. }
+        | ErrorKind::ServerSelection { .. } => true,
+        ErrorKind::Command(ref command) => CODES.contains(&command.code),
+        _ => false,
+    }
+}
+
+/// Like `ui::cancellable`, running `query` again while it fails because of a failover, until
+/// the new primary answers or `FAILOVER_TIMEOUT` has passed. Only for reads: the driver
+/// already retries writes once, and retrying them further could apply them twice.
+async fn through_failover<T, F>(query: impl Fn() -> F) -> Result<Option<mongodb::error::Result<T>>>
+where
+    F: std::future::Future<Output = mongodb::error::Result<T>>,
+{
+    let started = Instant::now();
+    let mut result = ui::cancellable(query()).await?;
+    while matches!(&result, Some(Err(e)) if is_failover(e)) && started.elapsed() < FAILOVER_TIMEOUT
+    {
+        let retry = async {
+            tokio::time::sleep(FAILOVER_RETRY).await;
+            query().await
+        };
+        result = ui::cancellable_as("failover in progress — reconnecting…", retry).await?;
+    }
+    Ok(result)
+}
+
+/// Whether the server aborted an operation because it ran past its `maxTimeMS`.
+fn is_max_time_expired(error: &mongodb::error::Error) -> bool {
+    matches!(*error.kind, ErrorKind::Command(ref command) if command.code == 50)
+}
+
+/// Copies the document with `id` to `trash` and deletes it from `collection`, in a transaction
+/// if the deployment supports them. Returns the moved document, or `None` if there wasn't one.
+async fn move_to_trash(
+    client: &Client,
+    collection: &Collection<Document>,
+    trash: &Collection<Document>,
+    id: Bson,
+) -> mongodb::error::Result<Option<Document>> {
+    let filter = doc! { "_id": id };
+    // Documents trashed before, e.g. deleted again once recovered, are replaced.
+    let options = ReplaceOptions::builder().upsert(true).build();
+    let mut session = client.start_session(None).await?;
+    // Standalone servers don't support transactions.
+    if session.start_transaction(None).await.is_err() {
+        let document = match collection.find_one(filter.clone(), None).await? {
+            Some(document) => document,
+            None => return Ok(None),
+        };
+        trash
+            .replace_one(filter.clone(), &document, options)
+            .await?;
+        collection.delete_one(filter, None).await?;
+        return Ok(Some(document));
+    }
+
+    let found = collection
+        .find_one_with_session(filter.clone(), None, &mut session)
+        .await?;
+    let document = match found {
+        Some(document) => document,
+        None => {
+            session.abort_transaction().await?;
+            return Ok(None);
+        }
+    };
+    trash
+        .replace_one_with_session(filter.clone(), &document, options, &mut session)
+        .await?;
+    collection
+        .delete_one_with_session(filter, None, &mut session)
+        .await?;
+    session.commit_transaction().await?;
+    Ok(Some(document))
+}
+
+/// Reads documents from `cursor` into `documents` until there are `count` of them or the
+/// cursor is exhausted.
+async fn fill(
+    cursor: &mut Cursor<Document>,
+    documents: &mut Vec<Document>,
+    count: usize,
+) -> mongodb::error::Result<()> {
+    while documents.len() < count {
+        match cursor.try_next().await? {
+            Some(document) => documents.push(document),
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// Counts the documents matching `filter`, settling for the metadata-based estimate on
+/// unfiltered collections unless `exact` is set, since counting those exactly means scanning
+/// them. Estimates can be off after an unclean shutdown.
+async fn count(
+    collection: &Collection<Document>,
+    filter: Option<Document>,
+    app: &App,
+    exact: bool,
+) -> mongodb::error::Result<Total> {
+    if filter.is_none() && !exact {
+        // Views can't be estimated, so they're counted like filtered collections.
+        if let Ok(estimate) = collection.estimated_document_count(None).await {
+            return Ok(Total {
+                count: estimate,
+                estimated: true,
+            });
+        }
+    }
+    let options = CountOptions::builder()
+        .collation(app.collation())
+        .hint(app.hint.clone())
+        .build();
+    let count = collection.count_documents(filter, options).await?;
+    Ok(Total {
+        count,
+        estimated: false,
+    })
+}
+
+/// Assigns each name its row, repeating the `pinned` ones in a section at the top. Returns
+/// the rows with how many pins were placed, leaving out the ones not among `names`.
+fn layout(names: &[String], pinned: &[String]) -> (Vec<(String, usize)>, usize) {
+    let pinned: Vec<&String> = names.iter().filter(|name| pinned.contains(name)).collect();
+    let placed = pinned.len();
+    let offset = match placed {
+        0 => 0,
+        // One row for the separator.
+        n => n + 1,
+    };
+    let rows = pinned
+        .into_iter()
+        .cloned()
+        .enumerate()
+        .map(|(i, x)| (x, i))
+        .chain(
+            names
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(i, x)| (x, i + offset)),
+        )
+        .collect();
+    (rows, placed)
+}
+
+/// The one of `names` that `name` was meant to be, ignoring case, or else `name` itself: when
+/// there's no such name, and when several differ only by case and none matches exactly.
+fn matching_name(names: &[String], name: &str) -> String {
+    if names.iter().any(|n| n == name) {
+        return name.to_string();
+    }
+    let lowercase = name.to_lowercase();
+    let mut matches = names.iter().filter(|n| n.to_lowercase() == lowercase);
+    match (matches.next(), matches.next()) {
+        (Some(only), None) => only.clone(),
+        _ => name.to_string(),
+    }
+}
+
+/// The row of `name` in a list laid out by `layout`, in the pinned section if it's pinned.
+fn row_of(list: &[(String, usize)], name: &str) -> Option<usize> {
+    list.iter().find(|(n, _)| n == name).map(|(_, row)| *row)
+}
+
+/// Prints a list laid out by `layout`, with `pinned` entries at the top.
+fn print_list(list: &[(String, usize)], pinned: usize, suffix: impl Fn(&str) -> String) {
+    for (name, i) in list {
+        if pinned > 0 && *i == pinned + 1 {
+            cprintln!("<dim>──</dim>");
+        }
+        if *i < pinned {
+            cprintln!("<yellow>*</yellow>  {}{}", name, suffix(name));
+        } else {
+            cprintln!("<green>></green>  {}{}", name, suffix(name));
+        }
+    }
+}
+
+/// What the main loop does after a key.
+#[derive(Debug, PartialEq)]
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// Handles a key pressed in `sessions[*active]`, the connection shown. `interrupted` tells
+/// whether the last key was a ctrl-c.
+async fn handle_key(
+    sessions: &mut Vec<App>,
+    active: &mut usize,
+    interrupted: &mut bool,
+    event: KeyEvent,
+    args: &Args,
+    config: &Config,
+) -> Result<Flow> {
+    // The first ctrl-c goes back to the databases, a second one in a row quits.
+    if ui::is_interrupt(&event) {
+        let app = &mut sessions[*active];
+        if *interrupted || matches!(app.state, State::Default) {
+            return Ok(Flow::Quit);
+        }
+        *interrupted = true;
+        app.reset().await?;
+        ui::status("ctrl-c again to quit")?;
+        return Ok(Flow::Continue);
+    }
+    *interrupted = false;
+
+    let switch_to = match event.code {
+        KeyCode::Tab if sessions.len() > 1 => Some((*active + 1) % sessions.len()),
+        KeyCode::Char(c @ '1'..='9') => c
+            .to_digit(10)
+            .map(|n| n as usize - 1)
+            .filter(|&n| n < sessions.len() && n != *active),
+        KeyCode::Char('C') => {
+            let connection_string = match ui::prompt("connect to: ")? {
+                Some(input) if !input.trim().is_empty() => input,
+                _ => return Ok(Flow::Continue),
+            };
+            match App::open(connection_string.trim(), args, config.clone()).await {
+                Ok(app) => {
+                    sessions.push(app);
+                    Some(sessions.len() - 1)
+                }
+                Err(e) => {
+                    ui::status(&format!("failed to connect: {e}"))?;
+                    None
+                }
+            }
+        }
+        KeyCode::Char('q') | KeyCode::Esc
+            if sessions.len() > 1 && matches!(sessions[*active].state, State::Default) =>
+        {
+            sessions.remove(*active).client.shutdown().await;
+            Some((*active).min(sessions.len() - 1))
+        }
+        _ => None,
+    };
+    if let Some(index) = switch_to {
+        *active = index;
+        let count = sessions.len();
+        for (i, session) in sessions.iter_mut().enumerate() {
+            session.tab = match count {
+                1 => String::new(),
+                _ => format!("{}/{count}", i + 1),
+            };
+        }
+        sessions[*active].redraw().await?;
+        return Ok(Flow::Continue);
+    }
+
+    let app = &mut sessions[*active];
+    if event.code == KeyCode::Char('w') {
+        app.copy_location()?;
+        return Ok(Flow::Continue);
+    }
+    match app.state {
+        State::Default => match event.code {
+            KeyCode::Char('P') => app.toggle_pin().await?,
+            KeyCode::Char('i') => app.show_info().await?,
+            KeyCode::Char(':') => app.run_command().await?,
+            KeyCode::Char('.') => app.rerun_last_query().await?,
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(Flow::Quit),
+            KeyCode::Char('/') => app.find_collections().await?,
+            KeyCode::Char('j') => execute!(io::stdout(), cursor::MoveDown(1))?,
+            KeyCode::Char('k') => execute!(io::stdout(), cursor::MoveUp(1))?,
+            KeyCode::Enter => {
+                // Some terminals report a stale or out of range position, e.g. after
+                // a resize, which no entry or only the separator of the pins is on.
+                let index = cursor::position()?.1 as usize;
+                let name = match app.list.iter().find(|item| item.1 == index) {
+                    Some(item) => item.0.clone(),
+                    None => return Ok(Flow::Continue),
+                };
+                if app.database_name != name {
+                    app.collection_name.clear();
+                }
+                app.database_search = None;
+                app.database_name = name.clone();
+                app.change_state(&State::InsideDatabase, Some(&name))
+                    .await?;
+                app.peek_pending = true;
+            }
+            _ => {}
+        },
+        State::InsideDatabase => match event.code {
+            KeyCode::Char('P') => app.toggle_pin().await?,
+            KeyCode::Char('j') => {
+                execute!(io::stdout(), cursor::MoveDown(1))?;
+                app.peek_pending = true;
+            }
+            KeyCode::Char('k') => {
+                execute!(io::stdout(), cursor::MoveUp(1))?;
+                app.peek_pending = true;
+            }
+            KeyCode::Char('i') => app.show_info().await?,
+            KeyCode::Char(':') => app.run_command().await?,
+            KeyCode::Char('.') => app.rerun_last_query().await?,
+            KeyCode::Char('D') => app.drop_collection().await?,
+            KeyCode::Char('F') => app.search_database().await?,
+            KeyCode::Char('q') if app.database_search.is_some() => {
+                app.database_search = None;
+                app.redraw().await?;
+            }
+            KeyCode::Char('q') => {
+                app.change_state(&State::Default, Some(&String::from("none")))
+                    .await?;
+            }
+            KeyCode::Enter => {
+                let index = (cursor::position()?.1 as usize).checked_sub(1);
+                let name = match app
+                    .collection_list
+                    .as_ref()
+                    .zip(index)
+                    .and_then(|(list, i)| list.iter().find(|(_, j)| *j == i))
+                {
+                    Some((name, _)) => name.clone(),
+                    None => return Ok(Flow::Continue),
+                };
+                let database = app.database.clone().unwrap();
+                if !app.confirm_open(&database, &name).await? {
+                    return Ok(Flow::Continue);
+                }
+                app.collection_name = name.clone();
+                if let Some((filter, _)) = &app.database_search {
+                    app.filter = Some(filter.clone());
+                }
+                app.change_state(&State::InsideCollection, Some(&name))
+                    .await?;
+            }
+            _ => {}
+        },
+        State::InsideCollection => match event.code {
+            KeyCode::Char('j') => execute!(io::stdout(), cursor::MoveDown(1))?,
+            KeyCode::Char('k') => execute!(io::stdout(), cursor::MoveUp(1))?,
+            KeyCode::Char('/') => app.text_search().await?,
+            KeyCode::Char('f') => app.set_filter().await?,
+            KeyCode::Char('g') => app.jump_to_id().await?,
+            KeyCode::Char('t') => app.set_max_time().await?,
+            KeyCode::Char('I') => app.show_indexes().await?,
+            KeyCode::Char('a') => app.aggregate().await?,
+            KeyCode::Char('R') => app.sample().await?,
+            KeyCode::Char('U') => app.update_many().await?,
+            KeyCode::Char(' ') => app.mark_for_comparison()?,
+            KeyCode::Char('E') => app.count_exactly().await?,
+            // Tab switches connections when there are several.
+            KeyCode::Tab | KeyCode::Char('b') => app.open_sidebar().await?,
+            KeyCode::Char('G') => app.group_by().await?,
+            KeyCode::Char('W') => app.watch().await?,
+            KeyCode::Enter => {
+                if let Some(document) = app.selected_document()? {
+                    app.document = Some(document.clone());
+                    app.tree = app.new_tree();
+                    app.change_state(&State::InsideDocument, None).await?;
+                }
+            }
+            KeyCode::Char('p') => {
+                app.render_mode = match app.render_mode {
+                    RenderMode::Pretty => RenderMode::Compact,
+                    _ => RenderMode::Pretty,
+                };
+                app.repaint()?;
+            }
+            KeyCode::Char('Y') => {
+                app.render_mode = match app.render_mode {
+                    RenderMode::Yaml => RenderMode::Compact,
+                    _ => RenderMode::Yaml,
+                };
+                app.repaint()?;
+            }
+            KeyCode::Char('F') => {
+                app.render_mode = match app.render_mode {
+                    RenderMode::Flat => RenderMode::Compact,
+                    _ => RenderMode::Flat,
+                };
+                app.repaint()?;
+            }
+            KeyCode::Char('M') => app.copy_markdown_table()?,
+            KeyCode::Char('c') => app.pick_columns()?,
+            KeyCode::Char('o') => app.open_url()?,
+            KeyCode::Char('m') => app.bookmark_selected()?,
+            KeyCode::Char('\'') => app.show_bookmarks().await?,
+            KeyCode::Char('T') => {
+                app.render_mode = match app.render_mode {
+                    RenderMode::Table => RenderMode::Compact,
+                    _ => RenderMode::Table,
+                };
+                app.h_scroll = 0;
+                app.repaint()?;
+            }
+            KeyCode::Char('s') => {
+                app.render_mode = match app.render_mode {
+                    RenderMode::Summary => RenderMode::Compact,
+                    _ => RenderMode::Summary,
+                };
+                app.repaint()?;
+            }
+            KeyCode::Left | KeyCode::Char('h') if app.h_scroll > 0 => {
+                app.h_scroll = app.h_scroll.saturating_sub(H_SCROLL_STEP);
+                app.repaint()?;
+            }
+            KeyCode::Right | KeyCode::Char('l')
+                if matches!(app.render_mode, RenderMode::Compact | RenderMode::Table) =>
+            {
+                app.h_scroll += H_SCROLL_STEP;
+                app.repaint()?;
+            }
+            KeyCode::Char('i') => app.show_info().await?,
+            KeyCode::Char(':') => app.run_command().await?,
+            KeyCode::Char('.') => app.rerun_last_query().await?,
+            KeyCode::Char('n') => app.change_page(1).await?,
+            KeyCode::Char('N') => app.change_page(-1).await?,
+            KeyCode::Char('#') => {
+                app.numbered = !app.numbered;
+                app.repaint()?;
+            }
+            KeyCode::Char('x') => {
+                app.json = app.json.toggled();
+                app.repaint()?;
+            }
+            KeyCode::Char('_') => {
+                app.hide_id = !app.hide_id;
+                app.repaint()?;
+            }
+            KeyCode::Char('z') => {
+                app.dates.timezone = match app.dates.timezone {
+                    Timezone::Local => Timezone::Utc,
+                    Timezone::Utc => Timezone::Local,
+                };
+                app.repaint()?;
+            }
+            KeyCode::Char('q') => {
+                app.reset_collection_view();
+                app.change_state(&State::InsideDatabase, Some(&app.database_name.clone()))
+                    .await?;
+                app.peek_pending = true;
+            }
+            _ => {}
+        },
+        State::InsideDocument => match event.code {
+            KeyCode::Char('j') => execute!(io::stdout(), cursor::MoveDown(1))?,
+            KeyCode::Char('k') => execute!(io::stdout(), cursor::MoveUp(1))?,
+            KeyCode::Enter | KeyCode::Char(' ') => app.toggle_selected_node()?,
+            KeyCode::Char('y') => app.copy_selected_value()?,
+            KeyCode::Char('s') => app.set_selected_field().await?,
+            KeyCode::Char('o') => app.open_url()?,
+            KeyCode::Char('m') => app.bookmark_selected()?,
+            KeyCode::Char('\'') => app.show_bookmarks().await?,
+            KeyCode::Char('r') => app.follow_reference().await?,
+            KeyCode::Char('f') => app.filter_by_selected_field().await?,
+            KeyCode::Char('e') => app.edit_document().await?,
+            KeyCode::Char('u') => app.update_document().await?,
+            KeyCode::Char('X') => app.delete_document().await?,
+            KeyCode::Char('x') => {
+                app.json = app.json.toggled();
+                app.repaint()?;
+            }
+            KeyCode::Char('_') => {
+                app.hide_id = !app.hide_id;
+                app.repaint()?;
+            }
+            KeyCode::Char('z') => {
+                app.dates.timezone = match app.dates.timezone {
+                    Timezone::Local => Timezone::Utc,
+                    Timezone::Utc => Timezone::Local,
+                };
+                app.repaint()?;
+            }
+            KeyCode::Char('i') => app.show_info().await?,
+            KeyCode::Char(':') => app.run_command().await?,
+            KeyCode::Char('.') => app.rerun_last_query().await?,
+            KeyCode::Char('q') => {
+                app.document = None;
+                app.change_state(&State::InsideCollection, Some(&app.collection_name.clone()))
+                    .await?;
+            }
+            _ => {}
+        },
+    }
+    Ok(Flow::Continue)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    // Any non-empty NO_COLOR disables colors, see https://no-color.org.
+    if args.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        style::disable_color();
+    }
+    let connection_string = args
+        .connect
+        .clone()
+        .or_else(|| std::env::var("TERMONGO_URI").ok())
+        .or_else(|| std::env::var("MONGODB_URI").ok())
+        .ok_or_else(|| anyhow!("no connection string, pass --connect or set TERMONGO_URI"))?;
+    if args.ping {
+        let (client, _, _) = connection::connect(&connection_string, &args).await?;
+        let round_trips = connection::ping(&client).await;
+        client.shutdown().await;
+        println!("{}", connection::describe_round_trips(&round_trips?));
+        return Ok(());
+    }
+    if !args.eval.is_empty() {
+        let (client, options, _) = connection::connect(&connection_string, &args).await?;
+        let result = eval::run(&client, &options, &args.eval).await;
+        client.shutdown().await;
+        return result;
+    }
+    if args.repl {
+        let (client, options, _) = connection::connect(&connection_string, &args).await?;
+        let result = repl::run(&client, &options).await;
+        client.shutdown().await;
+        return result;
+    }
+    let config = Config::load()?;
+    let mut sessions = vec![App::open(&connection_string, &args, config.clone()).await?];
+
+    ui::save_title()?;
+    terminal::enable_raw_mode().context("failed to put terminal in raw mode")?;
+    let result = run(&mut sessions, &args, &config).await;
+    close(sessions, result).await
+}
+
+/// Opens the database or collection the flags skip ahead to, then handles keys until one quits
+/// or handling one fails.
+async fn run(sessions: &mut Vec<App>, args: &Args, config: &Config) -> Result<()> {
+    // The first screen is drawn the way it's drawn again later on, with the cursor starting
+    // on the first database rather than wherever the shell left it.
+    execute!(io::stdout(), cursor::MoveTo(0, 0))?;
+    let mut active = 0;
+    let app = &mut sessions[active];
+    // Skipping ahead to a database or collection, q still goes back through the steps skipped.
+    let database = args
+        .database
+        .as_ref()
+        .map(|database| matching_name(&app.database_names, database));
+    match (database, &args.collection) {
+        (Some(database), Some(collection)) => {
+            app.open_namespace(database, collection.clone()).await?
+        }
+        (Some(database), None) => {
+            app.database_name = database.clone();
+            app.change_state(&State::InsideDatabase, Some(&database))
+                .await?
+        }
+        _ => app.change_state(&State::Default, None).await?,
+    }
+
+    let mut interrupted = false;
+    loop {
+        if !event::poll(PEEK_DELAY)? {
+            let app = &mut sessions[active];
+            if app.peek_pending && matches!(app.state, State::InsideDatabase) {
+                app.peek_pending = false;
+                app.peek().await?;
+            }
+            continue;
+        }
+
+        if let Event::Key(event) = event::read().context("failed to read a terminal event")? {
+            let flow =
+                handle_key(sessions, &mut active, &mut interrupted, event, args, config).await?;
+            if flow == Flow::Quit {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Takes the terminal out of raw mode, restores its title and shuts the connections down
+/// however the main loop ended, then passes on how it did, `result`.
+async fn close(sessions: Vec<App>, result: Result<()>) -> Result<()> {
+    let restored = terminal::disable_raw_mode()
+        .map_err(anyhow::Error::from)
+        .and_then(|()| ui::restore_title());
+    for session in sessions {
+        session.client.shutdown().await;
+    }
+    result.and(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+    use mongodb::error::CommandError;
+
+    /// An app in `state` on a connection nothing is sent to.
+    async fn app(state: State) -> App {
+        let args = Args::parse_from(["termongo"]);
+        let options = ClientOptions::parse("mongodb://localhost").await.unwrap();
+        let client = Client::with_options(options.clone()).unwrap();
+        let routers = Arc::default();
+        let mut app = App::new(
+            client,
+            options,
+            routers,
+            Vec::new(),
+            &args,
+            Config::default(),
+            false,
+        );
+        app.state = state;
+        app
+    }
+
+    /// Handles `key` in a connection in `state`.
+    async fn flow(state: State, key: KeyEvent, interrupted: bool) -> Flow {
+        let args = Args::parse_from(["termongo"]);
+        let app = app(state).await;
+        let mut interrupted = interrupted;
+        let config = Config::default();
+        handle_key(
+            &mut vec![app],
+            &mut 0,
+            &mut interrupted,
+            key,
+            &args,
+            &config,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn q_quits_from_the_databases() {
+        let key = KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert_eq!(flow(State::Default, key, false).await, Flow::Quit);
+    }
+
+    #[tokio::test]
+    async fn escape_quits_from_the_databases() {
+        let key = KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(flow(State::Default, key, false).await, Flow::Quit);
+    }
+
+    #[tokio::test]
+    async fn a_second_ctrl_c_quits() {
+        let key = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert_eq!(flow(State::InsideDocument, key, true).await, Flow::Quit);
+    }
+
+    #[tokio::test]
+    async fn invalid_regex_filters_are_reported() {
+        let error = mongodb::bson::from_document::<CommandError>(doc! {
+            "code": 51091,
+            "codeName": "Location51091",
+            "errmsg": "Regular expression is invalid: missing )",
+        })
+        .unwrap();
+        let error = mongodb::error::Error::from(ErrorKind::Command(error));
+        let message = app(State::InsideCollection)
+            .await
+            .describe_fetch_error(&error);
+        assert!(message.starts_with("query failed: "), "{message}");
+        assert!(
+            message.contains("Regular expression is invalid: missing )"),
+            "{message}"
+        );
+    }
+
+    #[tokio::test]
+    async fn errors_handling_keys_still_close_the_sessions() {
+        let app = app(State::InsideCollection).await;
+        let client = app.client.clone();
+        let failed = Err(anyhow!("failed to read a terminal event"));
+        let error = close(vec![app], failed).await.unwrap_err();
+        assert_eq!(error.to_string(), "failed to read a terminal event");
+        let listed = client.list_database_names(None, None).await;
+        assert!(matches!(listed, Err(e) if matches!(*e.kind, ErrorKind::Shutdown)));
+    }
+
+    #[tokio::test]
+    async fn cancelled_views_go_back_to_the_settings_on_screen() {
+        let mut app = app(State::InsideCollection).await;
+        app.filter = Some(doc! { "name": "a" });
+        app.page = 2;
+        app.shown = Some(Shown {
+            query: app.query(),
+            database: None,
+            database_search: None,
+            total: None,
+            document: None,
+        });
+        app.filter = Some(doc! { "name": "b" });
+        app.page = 0;
+        app.paging_forward = true;
+        app.restore_shown();
+        assert_eq!(app.filter, Some(doc! { "name": "a" }));
+        assert_eq!(app.page, 2);
+        assert!(!app.paging_forward);
+    }
 }
@@ -0,0 +1,188 @@
+use crate::{
+    connection::describe_write_error,
+    errors::{describe_unauthorized, is_unauthorized},
+    style::{cformat, cprintln},
+    ui,
+};
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::KeyCode,
+    execute,
+    terminal::{self, ClearType},
+};
+use mongodb::{
+    bson::{doc, Bson, Document},
+    Client,
+};
+use std::{io, time::Duration};
+
+/// How often the operations are fetched again.
+const REFRESH: Duration = Duration::from_secs(2);
+
+/// Lists the operations in progress, refreshing them periodically, until q or escape is pressed.
+/// Killing them is refused when `read_only` is set, and confirming a kill is cancelled after
+/// `confirm_timeout`. Expects the terminal to be in raw mode.
+pub async fn browse(
+    client: &Client,
+    read_only: bool,
+    confirm_timeout: Option<Duration>,
+) -> Result<()> {
+    let admin = client.database("admin");
+    // Without the inprog privilege, users may still see their own operations.
+    let mut own_only = false;
+    let mut operations = match list(client, own_only).await {
+        Err(e) if is_unauthorized(&e) => {
+            own_only = true;
+            list(client, own_only).await.map_err(describe)?
+        }
+        operations => operations.map_err(describe)?,
+    };
+    let mut message = String::new();
+    draw(&operations, own_only, None, &message)?;
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(REFRESH) => {
+                let selected = selected(&operations)?.map(|op| op.get("opid").cloned());
+                operations = list(client, own_only).await.map_err(describe)?;
+                draw(&operations, own_only, selected.flatten().as_ref(), &message)?;
+            }
+            key = ui::next_key() => {
+                let key = key?;
+                match key.code {
+                    _ if ui::is_interrupt(&key) => return Ok(()),
+                    KeyCode::Char('j') => execute!(io::stdout(), cursor::MoveDown(1))?,
+                    KeyCode::Char('k') => execute!(io::stdout(), cursor::MoveUp(1))?,
+                    KeyCode::Char('x') if read_only => ui::status("read-only mode")?,
+                    KeyCode::Char('x') => {
+                        let opid = match selected(&operations)?.and_then(|op| op.get("opid")) {
+                            Some(opid) => opid.clone(),
+                            None => continue,
+                        };
+                        let question = format!("kill operation {opid}?");
+                        if !ui::confirm_within(&question, confirm_timeout)? {
+                            continue;
+                        }
+                        let killed = admin
+                            .run_command(doc! { "killOp": 1, "op": opid.clone() }, None)
+                            .await;
+                        message = match killed {
+                            Ok(_) => format!("killed operation {opid}"),
+                            Err(e) if is_unauthorized(&e) => String::from(
+                                "not permitted to kill operations (requires the killop privilege)",
+                            ),
+                            Err(e) => format!(
+                                "failed to kill the operation: {}",
+                                describe_write_error(&e)
+                            ),
+                        };
+                        operations = list(client, own_only).await.map_err(describe)?;
+                        draw(&operations, own_only, Some(&opid), &message)?;
+                    }
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+async fn list(client: &Client, own_only: bool) -> mongodb::error::Result<Vec<Document>> {
+    let mut command = doc! { "currentOp": 1, "active": true };
+    if own_only {
+        command.insert("$ownOps", true);
+    }
+    let reply = client.database("admin").run_command(command, None).await?;
+    let mut operations: Vec<Document> = reply
+        .get_array("inprog")
+        .into_iter()
+        .flatten()
+        .filter_map(|op| op.as_document().cloned())
+        .collect();
+    operations.sort_by_key(|op| std::cmp::Reverse(running_micros(op)));
+    Ok(operations)
+}
+
+fn selected(operations: &[Document]) -> Result<Option<&Document>> {
+    let index = (cursor::position()?.1 as usize).checked_sub(1);
+    Ok(index.and_then(|i| operations.get(i)))
+}
+
+/// Draws the operations, longest running first, with the cursor on `selected` if it's still
+/// running, and `message` on the status line.
+fn draw(
+    operations: &[Document],
+    own_only: bool,
+    selected: Option<&Bson>,
+    message: &str,
+) -> Result<()> {
+    let row = cursor::position()?.1;
+    let (columns, _) = terminal::size()?;
+    terminal::disable_raw_mode()?;
+    print!(
+        "{}{}",
+        cursor::MoveTo(0, 0),
+        terminal::Clear(ClearType::All),
+    );
+    cprintln!(
+        "<yellow>current operations</yellow>{} <cyan>(x: kill, q: back)</cyan>",
+        match own_only {
+            true => " (own only)",
+            false => "",
+        }
+    );
+    for op in operations {
+        println!("{}", describe_operation(op, columns as usize));
+    }
+    let row = selected
+        .and_then(|opid| {
+            operations
+                .iter()
+                .position(|op| op.get("opid") == Some(opid))
+        })
+        .map_or(row.max(1), |i| i as u16 + 1);
+    execute!(io::stdout(), cursor::MoveTo(0, row))?;
+    terminal::enable_raw_mode()?;
+    ui::status(message)
+}
+
+/// Renders an operation on a single line: opid, duration, type, namespace and client.
+fn describe_operation(op: &Document, width: usize) -> String {
+    let opid = op.get("opid").map(Bson::to_string).unwrap_or_default();
+    let duration = format!("{:.1}s", running_micros(op) as f64 / 1_000_000.0);
+    let kind = op.get_str("op").unwrap_or("?");
+    let ns = op.get_str("ns").unwrap_or_default();
+    // mongos reports the client as `client_s`.
+    let client = op
+        .get_str("client")
+        .or_else(|_| op.get_str("client_s"))
+        .unwrap_or_default();
+    let client = match op.get_str("appName") {
+        Ok(app) => format!("{client} ({app})"),
+        Err(_) => client.to_string(),
+    };
+
+    let prefix = format!("{opid} {duration:>8} {kind:8} {ns} ");
+    let client = ui::single_line(&prefix, &client, width);
+    cformat!(
+        "<green>{}</green> {:>8} {:8} <yellow>{}</yellow> <dim>{}</dim>",
+        opid,
+        duration,
+        kind,
+        ns,
+        client
+    )
+}
+
+fn running_micros(op: &Document) -> i64 {
+    match op.get("microsecs_running") {
+        Some(Bson::Int64(micros)) => *micros,
+        Some(Bson::Int32(micros)) => *micros as i64,
+        _ => 0,
+    }
+}
+
+fn describe(error: mongodb::error::Error) -> anyhow::Error {
+    describe_unauthorized(error, "list operations")
+}
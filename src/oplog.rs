@@ -0,0 +1,119 @@
+use crate::{
+    errors::describe_unauthorized,
+    style::{cformat, cprint},
+    ui,
+};
+use anyhow::{anyhow, Result};
+use crossterm::{
+    cursor,
+    event::KeyCode,
+    terminal::{self, ClearType},
+};
+use futures::stream::{StreamExt, TryStreamExt};
+use mongodb::{
+    bson::{doc, Bson, Document},
+    options::{CursorType, FindOptions},
+    Client,
+};
+use std::time::Duration;
+
+/// How many of the latest entries are shown before tailing starts.
+const BACKLOG: i64 = 20;
+
+/// Prints the latest oplog entries, then new ones as they come in, until q or escape is pressed.
+/// Expects the terminal to be in raw mode.
+pub async fn tail(client: &Client) -> Result<()> {
+    let local = client.database("local");
+    let names = local
+        .list_collection_names(doc! { "name": "oplog.rs" })
+        .await
+        .map_err(describe)?;
+    if names.is_empty() {
+        return Err(anyhow!("no oplog, the server isn't a replica set member"));
+    }
+    let oplog = local.collection::<Document>("oplog.rs");
+
+    let options = FindOptions::builder()
+        .sort(doc! { "$natural": -1 })
+        .limit(BACKLOG)
+        .build();
+    let latest: Vec<Document> = oplog
+        .find(None, options)
+        .await
+        .map_err(describe)?
+        .try_collect()
+        .await?;
+
+    print!(
+        "{}{}",
+        cursor::MoveTo(0, 0),
+        terminal::Clear(ClearType::All),
+    );
+    cprint!("<yellow>local.oplog.rs</yellow> <cyan>tailing, q to stop</cyan>\r\n");
+    let (columns, _) = terminal::size()?;
+    for entry in latest.iter().rev() {
+        print!("{}\r\n", describe_entry(entry, columns as usize));
+    }
+
+    let filter = latest
+        .first()
+        .and_then(|entry| entry.get_timestamp("ts").ok())
+        .map(|ts| doc! { "ts": { "$gt": ts } });
+    let options = FindOptions::builder()
+        .cursor_type(CursorType::TailableAwait)
+        .max_await_time(Duration::from_secs(1))
+        .build();
+    let mut cursor = oplog.find(filter, options).await.map_err(describe)?;
+
+    loop {
+        tokio::select! {
+            entry = cursor.next() => match entry {
+                Some(entry) => print!("{}\r\n", describe_entry(&entry?, columns as usize)),
+                None => return Err(anyhow!("the oplog cursor was closed")),
+            },
+            key = ui::next_key() => {
+                let key = key?;
+                if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) || ui::is_interrupt(&key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn describe(error: mongodb::error::Error) -> anyhow::Error {
+    describe_unauthorized(error, "read the oplog")
+}
+
+/// Renders an entry on a single line: timestamp, operation, namespace and the affected document.
+fn describe_entry(entry: &Document, width: usize) -> String {
+    let ts = match entry.get("ts") {
+        Some(Bson::Timestamp(ts)) => format!("{}.{}", ts.time, ts.increment),
+        _ => String::from("?"),
+    };
+    let op = match entry.get_str("op").unwrap_or_default() {
+        "i" => "insert",
+        "u" => "update",
+        "d" => "delete",
+        "c" => "command",
+        "n" => "noop",
+        _ => "?",
+    };
+    let ns = entry.get_str("ns").unwrap_or_default();
+    // Updates keep the `_id` of the changed document in `o2` and the change in `o`.
+    let document = match (entry.get_document("o2"), entry.get_document("o")) {
+        (Ok(target), Ok(change)) => format!("{target} {change}"),
+        (_, Ok(document)) => document.to_string(),
+        _ => String::new(),
+    };
+
+    let prefix = format!("{ts} {op:7} {ns} ");
+    let document = ui::single_line(&prefix, &document, width);
+    cformat!(
+        "<dim>{}</dim> <green>{:7}</green> <yellow>{}</yellow> {}",
+        ts,
+        op,
+        ns,
+        document
+    )
+}
@@ -0,0 +1,234 @@
+use crate::{
+    connection::describe_write_error,
+    errors::describe_unauthorized,
+    style::{cformat, cprintln},
+    ui,
+};
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode},
+    execute,
+    terminal::{self, ClearType},
+};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Bson, Document},
+    options::FindOptions,
+    Database,
+};
+use std::{io, time::Duration};
+
+/// How many profiled operations are listed.
+const LIMIT: i64 = 200;
+
+/// Lists the operations the profiler recorded in `database`, slowest or latest first, until q
+/// or escape is pressed. 0, 1 and 2 set the profiling level after confirmation, which is
+/// refused when `read_only` is set, and cancelled after `confirm_timeout`. Expects the terminal
+/// to be in raw mode.
+pub async fn browse(
+    database: &Database,
+    read_only: bool,
+    confirm_timeout: Option<Duration>,
+) -> Result<()> {
+    let mut by_duration = true;
+    let mut operations = list(database, by_duration).await?;
+    let mut level = status(database).await?;
+    draw(database, &operations, &level, by_duration)?;
+
+    loop {
+        let event = match event::read()? {
+            Event::Key(event) => event,
+            _ => continue,
+        };
+        match event.code {
+            _ if ui::is_interrupt(&event) => return Ok(()),
+            KeyCode::Char('j') => execute!(io::stdout(), cursor::MoveDown(1))?,
+            KeyCode::Char('k') => execute!(io::stdout(), cursor::MoveUp(1))?,
+            KeyCode::Char('s') | KeyCode::Char('r') => {
+                if event.code == KeyCode::Char('s') {
+                    by_duration = !by_duration;
+                }
+                operations = list(database, by_duration).await?;
+                draw(database, &operations, &level, by_duration)?;
+            }
+            KeyCode::Char('0' | '1' | '2') if read_only => ui::status("read-only mode")?,
+            KeyCode::Char(c @ ('0' | '1' | '2')) => {
+                let new_level = c.to_digit(10).unwrap_or_default() as i32;
+                let message = set_level(database, new_level, None, confirm_timeout).await?;
+                level = status(database).await?;
+                draw(database, &operations, &level, by_duration)?;
+                ui::status(&message)?;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Sets the profiling level of `database`, and optionally the threshold of slow operations,
+/// once the user confirms it. Returns the message to report.
+pub async fn set_level(
+    database: &Database,
+    level: i32,
+    slow_ms: Option<i64>,
+    confirm_timeout: Option<Duration>,
+) -> Result<String> {
+    let question = match level {
+        0 => format!("turn the profiler of {} off?", database.name()),
+        1 => format!("profile slow operations in {}?", database.name()),
+        _ => format!("profile every operation in {}?", database.name()),
+    };
+    if !ui::confirm_within(&question, confirm_timeout)? {
+        return Ok(String::from("profiling level not changed"));
+    }
+    let mut command = doc! { "profile": level };
+    if let Some(slow_ms) = slow_ms {
+        command.insert("slowms", slow_ms);
+    }
+    Ok(match database.run_command(command, None).await {
+        Ok(_) => format!("profiling level set to {level}"),
+        Err(e) => format!(
+            "failed to set the profiling level: {}",
+            describe_write_error(&e)
+        ),
+    })
+}
+
+/// The profiling level and slow operation threshold, e.g. `level 1, slower than 100ms`.
+async fn status(database: &Database) -> Result<String> {
+    let reply = database
+        .run_command(doc! { "profile": -1 }, None)
+        .await
+        .map_err(describe)?;
+    let level = reply.get("was").map(Bson::to_string).unwrap_or_default();
+    let slow_ms = reply.get("slowms").map(Bson::to_string).unwrap_or_default();
+    Ok(match level.as_str() {
+        "0" => String::from("profiler off"),
+        "1" => format!("level 1, slower than {slow_ms}ms"),
+        _ => format!("level {level}, every operation"),
+    })
+}
+
+async fn list(database: &Database, by_duration: bool) -> Result<Vec<Document>> {
+    let sort = match by_duration {
+        true => doc! { "millis": -1 },
+        false => doc! { "ts": -1 },
+    };
+    let options = FindOptions::builder().sort(sort).limit(LIMIT).build();
+    // Without profiling, there's no `system.profile` and so nothing found.
+    let operations = database
+        .collection::<Document>("system.profile")
+        .find(None, options)
+        .await
+        .map_err(describe)?
+        .try_collect()
+        .await?;
+    Ok(operations)
+}
+
+fn draw(
+    database: &Database,
+    operations: &[Document],
+    level: &str,
+    by_duration: bool,
+) -> Result<()> {
+    let row = cursor::position()?.1;
+    let (columns, _) = terminal::size()?;
+    terminal::disable_raw_mode()?;
+    print!(
+        "{}{}",
+        cursor::MoveTo(0, 0),
+        terminal::Clear(ClearType::All),
+    );
+    cprintln!(
+        "<yellow>{}.system.profile</yellow> ({}, {}) <cyan>(s: sort, r: refresh, 0-2: level, q: back)</cyan>",
+        database.name(),
+        level,
+        match by_duration {
+            true => "slowest first",
+            false => "latest first",
+        }
+    );
+    if operations.is_empty() {
+        println!("no profiled operations, press 1 to profile slow operations");
+    }
+    for operation in operations {
+        println!("{}", describe_operation(operation, columns as usize));
+    }
+    execute!(io::stdout(), cursor::MoveTo(0, row.max(1)))?;
+    terminal::enable_raw_mode()?;
+    Ok(())
+}
+
+/// Renders a profiled operation on a single line: time, duration, type, namespace and the
+/// shape of its query.
+fn describe_operation(operation: &Document, width: usize) -> String {
+    let time = match operation.get_datetime("ts") {
+        Ok(ts) => ts
+            .try_to_rfc3339_string()
+            .unwrap_or_else(|_| ts.to_string()),
+        Err(_) => String::from("?"),
+    };
+    let millis = match operation.get("millis") {
+        Some(Bson::Int32(millis)) => format!("{millis}ms"),
+        Some(Bson::Int64(millis)) => format!("{millis}ms"),
+        _ => String::from("?"),
+    };
+    let op = operation.get_str("op").unwrap_or("?");
+    let ns = operation.get_str("ns").unwrap_or_default();
+    let shape = match operation.get_document("command") {
+        Ok(command) => shape(&Bson::Document(command.clone())).to_string(),
+        Err(_) => String::new(),
+    };
+
+    let prefix = format!("{time} {millis:>8} {op:8} {ns} ");
+    let shape = ui::single_line(&prefix, &shape, width);
+    cformat!(
+        "<dim>{}</dim> <green>{:>8}</green> {:8} <yellow>{}</yellow> {}",
+        time,
+        millis,
+        op,
+        ns,
+        shape
+    )
+}
+
+/// `value` with the values in filters replaced by `1`, so operations that only differ by
+/// them look the same. The names of the command and collection, and of the fields, stay.
+fn shape(value: &Bson) -> Bson {
+    match value {
+        Bson::Document(document) => Bson::Document(
+            document
+                .iter()
+                .filter(|(key, _)| !matches!(key.as_str(), "lsid" | "$clusterTime" | "$db"))
+                .map(|(key, value)| match value {
+                    Bson::String(_) if is_name(key) => (key.clone(), value.clone()),
+                    value => (key.clone(), shape(value)),
+                })
+                .collect(),
+        ),
+        Bson::Array(items) => Bson::Array(items.iter().map(shape).collect()),
+        _ => Bson::Int32(1),
+    }
+}
+
+/// Whether the string value of `key` names a collection, e.g. in `{find: "users"}`.
+fn is_name(key: &str) -> bool {
+    matches!(
+        key,
+        "find"
+            | "aggregate"
+            | "count"
+            | "distinct"
+            | "update"
+            | "delete"
+            | "insert"
+            | "findAndModify"
+            | "getMore"
+    )
+}
+
+fn describe(error: mongodb::error::Error) -> anyhow::Error {
+    describe_unauthorized(error, "read the profiler")
+}
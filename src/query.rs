@@ -0,0 +1,379 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::style::Stylize;
+use mongodb::{
+    bson::{Bson, Document, Regex},
+    options::UpdateModifications,
+};
+use serde_json::Value;
+
+/// Parses a document typed the way the mongo shell accepts it, e.g. `{email: 1}`,
+/// `{name: 'x'}`, `{name: /^x/i}`, `{price: NumberDecimal("10.99")}`,
+/// `{_id: UUID("00112233-4455-6677-8899-aabbccddeeff")}` or `{ts: Timestamp(1700000000, 1)}`,
+/// as well as strict extended JSON.
+pub fn parse_document(input: &str) -> Result<Document, String> {
+    match parse(input)? {
+        Bson::Document(document) => Ok(document),
+        other => Err(format!("expected a document, got {other}")),
+    }
+}
+
+/// Parses an aggregation pipeline, an array of stages, in the same syntax as `parse_document`.
+/// A single stage may be given without the brackets.
+pub fn parse_pipeline(input: &str) -> Result<Vec<Document>, String> {
+    match parse(input)? {
+        Bson::Array(stages) => stages
+            .into_iter()
+            .map(|stage| match stage {
+                Bson::Document(stage) => Ok(stage),
+                other => Err(format!("expected a stage, got {other}")),
+            })
+            .collect(),
+        Bson::Document(stage) => Ok(vec![stage]),
+        other => Err(format!("expected an array of stages, got {other}")),
+    }
+}
+
+/// Parses a single value, e.g. `'x'`, `[1, 2]` or `NumberDecimal("10.99")`, in the same
+/// syntax as `parse_document`.
+pub fn parse_value(input: &str) -> Result<Bson, String> {
+    parse(input)
+}
+
+fn parse(input: &str) -> Result<Bson, String> {
+    let value: Value = match serde_json::from_str(input) {
+        Ok(value) => value,
+        Err(_) => {
+            let (json, origins) = to_json(input);
+            serde_json::from_str(&json).map_err(|e| describe_error(input, &json, &origins, e))?
+        }
+    };
+    Bson::try_from(value)
+        .map(regexes)
+        .map_err(|e| e.to_string())
+}
+
+/// Turns `{"$regex": "…", "$options": "…"}` into regular expressions, which the extended JSON
+/// parser leaves as documents.
+fn regexes(value: Bson) -> Bson {
+    match value {
+        Bson::Document(document) => {
+            let is_regex = document.get_str("$regex").is_ok()
+                && document
+                    .keys()
+                    .all(|key| key == "$regex" || key == "$options");
+            if is_regex {
+                // The options of BSON regular expressions have to be sorted.
+                let mut options: Vec<char> = document
+                    .get_str("$options")
+                    .unwrap_or_default()
+                    .chars()
+                    .collect();
+                options.sort_unstable();
+                return Bson::RegularExpression(Regex {
+                    pattern: document.get_str("$regex").unwrap_or_default().to_string(),
+                    options: options.into_iter().collect(),
+                });
+            }
+            Bson::Document(
+                document
+                    .into_iter()
+                    .map(|(key, value)| (key, regexes(value)))
+                    .collect(),
+            )
+        }
+        Bson::Array(items) => Bson::Array(items.into_iter().map(regexes).collect()),
+        other => other,
+    }
+}
+
+/// Quotes bare keys, turns single-quoted strings into double-quoted ones,
+/// `/pattern/options` literals into `$regex` documents and `NumberDecimal("…")`, `UUID("…")` and
+/// `LUUID("…")` into their extended JSON.
+/// Also returns where each token of the JSON comes from, as (JSON offset, input offset) pairs.
+fn to_json(input: &str) -> (String, Vec<(usize, usize)>) {
+    let mut json = String::with_capacity(input.len());
+    let mut origins = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        origins.push((json.len(), at));
+        match c {
+            '"' | '\'' => {
+                let quote = c;
+                json.push('"');
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => match chars.next().map(|(_, c)| c) {
+                            Some('\'') => json.push('\''),
+                            Some(escaped) => {
+                                json.push('\\');
+                                json.push(escaped);
+                            }
+                            None => {}
+                        },
+                        c if c == quote => break,
+                        '"' => json.push_str("\\\""),
+                        c => json.push(c),
+                    }
+                }
+                json.push('"');
+            }
+            '/' => {
+                let mut pattern = String::new();
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            pattern.push(c);
+                            pattern.extend(chars.next().map(|(_, c)| c));
+                        }
+                        '/' => break,
+                        c => pattern.push(c),
+                    }
+                }
+                let mut options = String::new();
+                while let Some(&(_, c)) = chars.peek().filter(|(_, c)| c.is_ascii_alphabetic()) {
+                    options.push(c);
+                    chars.next();
+                }
+                json.push_str(&format!(
+                    "{{\"$regex\": {}, \"$options\": \"{options}\"}}",
+                    Value::from(pattern)
+                ));
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let mut word = String::from(c);
+                while let Some(&(_, next)) = chars.peek() {
+                    if next.is_alphanumeric() || matches!(next, '_' | '$' | '.') {
+                        word.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                while chars.peek().is_some_and(|(_, c)| c.is_whitespace()) {
+                    chars.next();
+                }
+                if chars.peek().map(|&(_, c)| c) == Some(':') {
+                    json.push_str(&format!("\"{word}\""));
+                } else if CONSTRUCTORS.contains(&word.as_str())
+                    && chars.peek().map(|&(_, c)| c) == Some('(')
+                {
+                    let argument: String = chars
+                        .by_ref()
+                        .skip(1)
+                        .map(|(_, c)| c)
+                        .take_while(|&c| c != ')')
+                        .collect();
+                    let argument = argument.trim().trim_matches(|c| c == '"' || c == '\'');
+                    json.push_str(&construct(&word, argument));
+                } else {
+                    json.push_str(&word);
+                }
+            }
+            c => json.push(c),
+        }
+    }
+    (json, origins)
+}
+
+/// Shell constructors `to_json` turns into extended JSON.
+const CONSTRUCTORS: [&str; 4] = ["NumberDecimal", "UUID", "LUUID", "Timestamp"];
+
+/// The extended JSON for one of the `CONSTRUCTORS`: `UUID("…")` makes a subtype 4 binary and
+/// `LUUID("…")` a legacy subtype 3 one, with the bytes in the order they're written in, and
+/// `Timestamp(seconds, increment)` a BSON timestamp.
+fn construct(name: &str, argument: &str) -> String {
+    let value = Value::from(argument);
+    match name {
+        "NumberDecimal" => format!("{{\"$numberDecimal\": {value}}}"),
+        // Left as they are, so anything but numbers is a syntax error.
+        "Timestamp" => {
+            let (time, increment) = argument.split_once(',').unwrap_or((argument, ""));
+            format!(
+                "{{\"$timestamp\": {{\"t\": {}, \"i\": {}}}}}",
+                time.trim(),
+                increment.trim()
+            )
+        }
+        "LUUID" => match parse_hex(&argument.replace('-', "")) {
+            Some(bytes) if bytes.len() == 16 => format!(
+                "{{\"$binary\": {{\"base64\": \"{}\", \"subType\": \"03\"}}}}",
+                STANDARD.encode(bytes)
+            ),
+            // Left for the UUID parser to explain what's wrong with it.
+            _ => format!("{{\"$uuid\": {value}}}"),
+        },
+        _ => format!("{{\"$uuid\": {value}}}"),
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Describes a syntax error in the JSON made from `input` by `to_json`, pointing at the
+/// position in `input` it comes from.
+fn describe_error(
+    input: &str,
+    json: &str,
+    origins: &[(usize, usize)],
+    error: serde_json::Error,
+) -> String {
+    let message = error.to_string();
+    let message = message.split(" at line ").next().unwrap_or_default();
+    // Lines and columns are counted in the JSON, starting at 1.
+    let offset: usize = json
+        .split_inclusive('\n')
+        .take(error.line().saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>()
+        + error.column().saturating_sub(1);
+    let at = match offset < json.len() {
+        true => origins
+            .iter()
+            .rev()
+            .find(|(json_offset, _)| *json_offset <= offset)
+            .map_or(0, |&(_, at)| at),
+        // The input ended too soon.
+        false => input.len(),
+    };
+    let column = input[..at].chars().count() + 1;
+    let (before, after) = input.split_at(at);
+    let mut after = after.chars();
+    let marked = match after.next() {
+        Some(c) => format!("{before}{}{}", c.to_string().reverse(), after.as_str()),
+        None => format!("{before}{}", " ".reverse()),
+    };
+    format!("{message} at column {column}: {marked}")
+}
+
+/// Makes a validator for `ui::prompt_json` out of `parse`. Empty input is left to the prompt,
+/// which usually gives it a meaning of its own.
+pub fn check<T>(parse: fn(&str) -> Result<T, String>) -> impl Fn(&str) -> Result<(), String> {
+    move |input| match input.trim() {
+        "" => Ok(()),
+        input => parse(input).map(|_| ()),
+    }
+}
+
+/// Parses an update: a pipeline if it's an array, otherwise a document of update operators.
+pub fn parse_update(input: &str) -> Result<UpdateModifications, String> {
+    match input.trim().starts_with('[') {
+        true => parse_pipeline(input).map(UpdateModifications::Pipeline),
+        false => parse_document(input).map(UpdateModifications::Document),
+    }
+}
+
+/// Counts the brackets and braces left open in `input`, e.g. `2 { 1 [ open`,
+/// or reports the first one closed without being opened.
+pub fn balance(input: &str) -> String {
+    if input.trim().is_empty() {
+        return String::new();
+    }
+    let mut open = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    for c in input.chars() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '{' | '[' => open.push(c),
+            '}' | ']' => {
+                let expected = if c == '}' { '{' } else { '[' };
+                if open.pop() != Some(expected) {
+                    return format!("unexpected {c}");
+                }
+            }
+            _ => {}
+        }
+    }
+    if quote.is_some() {
+        return String::from("unterminated string");
+    }
+    let braces = open.iter().filter(|&&c| c == '{').count();
+    let brackets = open.len() - braces;
+    match (braces, brackets) {
+        (0, 0) => String::from("balanced"),
+        (braces, 0) => format!("{braces} {{ open"),
+        (0, brackets) => format!("{brackets} [ open"),
+        (braces, brackets) => format!("{braces} {{ {brackets} [ open"),
+    }
+}
+
+/// The dotted paths of the fields in `documents`, e.g. `address.city`, in the order they first
+/// appear. Documents in arrays add their fields under the array's path, the way queries see them.
+pub fn field_paths(documents: &[Document]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for document in documents {
+        push_paths(&mut paths, "", document);
+    }
+    paths
+}
+
+fn push_paths(paths: &mut Vec<String>, prefix: &str, document: &Document) {
+    for (key, value) in document {
+        let path = format!("{prefix}{key}");
+        let nested: Vec<&Document> = match value {
+            Bson::Document(document) => vec![document],
+            Bson::Array(items) => items.iter().filter_map(Bson::as_document).collect(),
+            _ => Vec::new(),
+        };
+        if !paths.contains(&path) {
+            paths.push(path.clone());
+        }
+        for document in nested {
+            push_paths(paths, &format!("{path}."), document);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name_regex(filter: &str) -> Bson {
+        parse_document(filter).unwrap().get("name").unwrap().clone()
+    }
+
+    #[test]
+    fn regex_literals_parse_to_regular_expressions() {
+        let expected = Bson::RegularExpression(Regex {
+            pattern: String::from("^a"),
+            options: String::from("ix"),
+        });
+        assert_eq!(name_regex("{name: /^a/xi}"), expected);
+    }
+
+    #[test]
+    fn regex_operators_parse_to_regular_expressions() {
+        let expected = Bson::RegularExpression(Regex {
+            pattern: String::from("^a"),
+            options: String::from("ix"),
+        });
+        assert_eq!(
+            name_regex(r#"{name: {$regex: "^a", $options: "xi"}}"#),
+            expected
+        );
+    }
+
+    #[test]
+    fn regex_literals_round_trip() {
+        let (json, _) = to_json("{name: /^a/i}");
+        assert_eq!(json, r#"{"name": {"$regex": "^a", "$options": "i"}}"#);
+        assert_eq!(name_regex("{name: /^a/i}").to_string(), "/^a/i");
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{format::StrftimeItems, Local, TimeZone, Utc};
+use clap::ValueEnum;
+use mongodb::bson::{Bson, DateTime, Document};
+use serde::{Deserialize, Serialize};
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+use std::{fmt, str::FromStr};
+
+/// Indentation used by the pretty renderers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "Value", into = "Value")]
+pub enum Indent {
+    Spaces(usize),
+    Tab,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(2)
+    }
+}
+
+impl Indent {
+    /// The whitespace for `depth` levels of nesting.
+    pub fn repeat(&self, depth: usize) -> String {
+        self.to_string().repeat(depth)
+    }
+}
+
+impl fmt::Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Indent::Spaces(width) => write!(f, "{:width$}", ""),
+            Indent::Tab => write!(f, "\t"),
+        }
+    }
+}
+
+impl FromStr for Indent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tab" => Ok(Indent::Tab),
+            width => width
+                .parse()
+                .map(Indent::Spaces)
+                .map_err(|_| format!("expected a number of spaces or `tab`, got `{width}`")),
+        }
+    }
+}
+
+impl TryFrom<Value> for Indent {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(width) => width
+                .as_u64()
+                .map(|width| Indent::Spaces(width as usize))
+                .ok_or_else(|| format!("invalid indent width {width}")),
+            Value::String(s) => s.parse(),
+            other => Err(format!("expected a number of spaces or `tab`, got {other}")),
+        }
+    }
+}
+
+impl From<Indent> for Value {
+    fn from(indent: Indent) -> Self {
+        match indent {
+            Indent::Spaces(width) => Value::from(width),
+            Indent::Tab => Value::from("tab"),
+        }
+    }
+}
+
+/// Flavour of extended JSON documents are shown in.
+#[derive(Clone, Copy)]
+pub enum Json {
+    /// Plain JSON values where they don't lose information, e.g. `1` for an int32.
+    Relaxed,
+    /// Every value wrapped with its type, e.g. `{"$numberInt": "1"}`.
+    Canonical,
+}
+
+impl Json {
+    pub fn to_value(self, document: &Document) -> Value {
+        let document = Bson::Document(document.clone());
+        match self {
+            Json::Relaxed => {
+                let mut value = document.into_relaxed_extjson();
+                show_uuids(&mut value);
+                value
+            }
+            Json::Canonical => document.into_canonical_extjson(),
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            Json::Relaxed => Json::Canonical,
+            Json::Canonical => Json::Relaxed,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Relaxed => write!(f, "relaxed"),
+            Json::Canonical => write!(f, "canonical"),
+        }
+    }
+}
+
+/// Rewrites subtype 4 binaries in relaxed extended JSON as `{"$uuid": "…"}`, which parses back
+/// to the same binary.
+fn show_uuids(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            let uuid = fields
+                .get("$binary")
+                .filter(|_| fields.len() == 1)
+                .filter(|binary| binary["subType"] == "04")
+                .and_then(|binary| binary["base64"].as_str())
+                .and_then(|base64| STANDARD.decode(base64).ok())
+                .and_then(|bytes| uuid(&bytes));
+            match uuid {
+                Some(uuid) => *value = serde_json::json!({ "$uuid": uuid }),
+                None => fields.values_mut().for_each(show_uuids),
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(show_uuids),
+        _ => {}
+    }
+}
+
+/// Formats 16 bytes as a hyphenated UUID, e.g. `00112233-4455-6677-8899-aabbccddeeff`.
+/// Legacy subtype 3 UUIDs are formatted in the order their bytes are stored in, which only
+/// matches the UUID the application sees for drivers that didn't reorder them, e.g. Python's.
+/// The Java and C# drivers stored them with some of the bytes reversed.
+pub fn uuid(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 16 {
+        return None;
+    }
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    Some(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+/// Time zone dates are shown in.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Timezone {
+    Local,
+    Utc,
+}
+
+impl fmt::Display for Timezone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Timezone::Local => write!(f, "local"),
+            Timezone::Utc => write!(f, "utc"),
+        }
+    }
+}
+
+/// How dates are shown in relaxed JSON: in `timezone`, formatted with the strftime `format`.
+#[derive(Clone)]
+pub struct Dates {
+    pub format: String,
+    pub timezone: Timezone,
+}
+
+impl Dates {
+    pub fn show(&self, date: DateTime) -> String {
+        let date = match Utc.timestamp_millis_opt(date.timestamp_millis()).single() {
+            Some(date) => date,
+            None => return date.to_string(),
+        };
+        match self.timezone {
+            Timezone::Local => date.with_timezone(&Local).format(&self.format).to_string(),
+            Timezone::Utc => date.format(&self.format).to_string(),
+        }
+    }
+
+    /// Rewrites the `{"$date": …}` values of relaxed extended JSON as formatted dates, which
+    /// makes the JSON unfit for parsing back.
+    pub fn apply(&self, value: &mut Value) {
+        match value {
+            Value::Object(fields) if fields.len() == 1 && fields.contains_key("$date") => {
+                let millis = match &fields["$date"] {
+                    Value::String(date) => chrono::DateTime::parse_from_rfc3339(date)
+                        .ok()
+                        .map(|date| date.timestamp_millis()),
+                    Value::Object(long) => long
+                        .get("$numberLong")
+                        .and_then(Value::as_str)
+                        .and_then(|n| n.parse().ok()),
+                    _ => None,
+                };
+                if let Some(millis) = millis {
+                    fields["$date"] = Value::from(self.show(DateTime::from_millis(millis)));
+                }
+            }
+            Value::Object(fields) => fields.values_mut().for_each(|value| self.apply(value)),
+            Value::Array(items) => items.iter_mut().for_each(|value| self.apply(value)),
+            _ => {}
+        }
+    }
+}
+
+/// Checks a strftime format for dates, e.g. `%Y-%m-%d %H:%M`.
+pub fn parse_date_format(format: &str) -> Result<String, String> {
+    match StrftimeItems::new(format).any(|item| item == chrono::format::Item::Error) {
+        true => Err(format!("invalid date format `{format}`")),
+        false => Ok(format.to_string()),
+    }
+}
+
+/// Serializes `value` as YAML. Extended JSON wrappers like `$oid` stay, as nested mappings.
+pub fn yaml(value: &Value) -> Result<String> {
+    Ok(serde_yaml::to_string(value)?)
+}
+
+/// Like `pretty`, with the subdocuments and arrays nested `max_depth` deep, unless it's 0,
+/// shown as `{…}` and `[…]`.
+pub fn pretty_within(value: &Value, indent: Indent, max_depth: usize) -> Result<String> {
+    if max_depth == 0 {
+        return pretty(value, indent);
+    }
+    let text = pretty(&cut(value, max_depth), indent)?;
+    Ok(text
+        .replace(&Value::from(CUT_DOCUMENT).to_string(), "{…}")
+        .replace(&Value::from(CUT_ARRAY).to_string(), "[…]"))
+}
+
+/// Strings standing in for what `cut` leaves out, until they're replaced after serializing.
+const CUT_DOCUMENT: &str = "\u{0}{…}";
+const CUT_ARRAY: &str = "\u{0}[…]";
+
+/// `value` with its non-empty subdocuments and arrays `depth` levels down replaced by
+/// `CUT_DOCUMENT` and `CUT_ARRAY`.
+fn cut(value: &Value, depth: usize) -> Value {
+    match value {
+        Value::Object(fields) if depth == 0 && !fields.is_empty() => Value::from(CUT_DOCUMENT),
+        Value::Array(items) if depth == 0 && !items.is_empty() => Value::from(CUT_ARRAY),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), cut(value, depth - 1)))
+                .collect(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|item| cut(item, depth - 1)).collect())
+        }
+        value => value.clone(),
+    }
+}
+
+/// The values in `document` by dotted path, e.g. `address.city` or `tags.0`, in order. Empty
+/// subdocuments and arrays are values of their own.
+pub fn flatten(document: &Document) -> Vec<(String, Bson)> {
+    let mut values = Vec::new();
+    for (key, value) in document {
+        push_flattened(&mut values, key.clone(), value);
+    }
+    values
+}
+
+fn push_flattened(values: &mut Vec<(String, Bson)>, path: String, value: &Bson) {
+    match value {
+        Bson::Document(document) if !document.is_empty() => {
+            for (key, value) in document {
+                push_flattened(values, format!("{path}.{key}"), value);
+            }
+        }
+        Bson::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                push_flattened(values, format!("{path}.{i}"), item);
+            }
+        }
+        value => values.push((path, value.clone())),
+    }
+}
+
+/// Serializes `value` as multi-line JSON indented with `indent`.
+pub fn pretty(value: &Value, indent: Indent) -> Result<String> {
+    let indent = indent.to_string();
+    let mut buffer = Vec::new();
+    let mut serializer =
+        Serializer::with_formatter(&mut buffer, PrettyFormatter::with_indent(indent.as_bytes()));
+    value.serialize(&mut serializer)?;
+    Ok(String::from_utf8(buffer)?)
+}
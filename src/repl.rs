@@ -0,0 +1,152 @@
+use crate::{
+    config::Config,
+    eval,
+    style::{cformat, cprintln},
+    ui,
+};
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyModifiers},
+    execute,
+    terminal::{self, ClearType},
+};
+use mongodb::{options::ClientOptions, Client};
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+/// Lines kept in the history file.
+const HISTORY_SIZE: usize = 500;
+
+/// Reads queries in the syntax `--eval` takes from a prompt and prints their results, until
+/// `exit` or ctrl-d. `use <db>` switches databases and `show dbs` and `show collections` list
+/// them. Up and down go through the history, which is kept next to the config file.
+pub async fn run(client: &Client, options: &ClientOptions) -> Result<()> {
+    let mut database = options
+        .default_database
+        .clone()
+        .unwrap_or_else(|| String::from("test"));
+    let path = history_path();
+    let mut history: Vec<String> = path
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|text| text.lines().map(String::from).collect())
+        .unwrap_or_default();
+    cprintln!(
+        "<yellow>termongo</yellow> <cyan>db.<<collection>>.find(…), findOne(…) and count(…), \
+         use <<db>>, show dbs, show collections, exit</cyan>"
+    );
+
+    loop {
+        let line = match read_line(&format!("{database}> "), &history)? {
+            Some(line) => line.trim().to_string(),
+            None => break,
+        };
+        if line.is_empty() {
+            continue;
+        }
+        history.retain(|previous| *previous != line);
+        history.push(line.clone());
+
+        let output = match line.as_str() {
+            "exit" | "quit" => break,
+            "show dbs" | "show databases" => client
+                .list_database_names(None, None)
+                .await
+                .map(|names| names.join("\n"))
+                .map_err(anyhow::Error::from),
+            "show collections" => client
+                .database(&database)
+                .list_collection_names(None)
+                .await
+                .map(|mut names| {
+                    names.sort();
+                    names.join("\n")
+                })
+                .map_err(anyhow::Error::from),
+            _ if line.starts_with("use ") => {
+                let names = client
+                    .list_database_names(None, None)
+                    .await
+                    .unwrap_or_default();
+                database = crate::matching_name(&names, line["use ".len()..].trim());
+                Ok(format!("switched to db {database}"))
+            }
+            _ => match eval::evaluate(&client.database(&database), &line).await {
+                Ok(value) => serde_json::to_string_pretty(&value).map_err(anyhow::Error::from),
+                Err(e) => Err(e),
+            },
+        };
+        match output {
+            Ok(output) => println!("{output}"),
+            Err(e) => println!("{}", cformat!("<red>{}</red>", e)),
+        }
+    }
+
+    if let Some(path) = path {
+        let kept = &history[history.len().saturating_sub(HISTORY_SIZE)..];
+        let saved = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&path, kept.join("\n")));
+        if let Err(e) = saved {
+            eprintln!("failed to save the history to {}: {e}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn history_path() -> Option<PathBuf> {
+    Some(Config::path()?.parent()?.join("history"))
+}
+
+/// Reads a line after `label`, going through `history` with up and down. Returns `None` on
+/// ctrl-d with an empty line, and an empty line on ctrl-c.
+fn read_line(label: &str, history: &[String]) -> Result<Option<String>> {
+    let mut stdout = io::stdout();
+    let mut input = String::new();
+    // Position in the history, which is `history.len()` for the line being typed.
+    let mut position = history.len();
+    terminal::enable_raw_mode()?;
+    let line = loop {
+        execute!(
+            stdout,
+            cursor::MoveToColumn(0),
+            terminal::Clear(ClearType::CurrentLine)
+        )?;
+        print!("{label}{input}");
+        stdout.flush()?;
+
+        if let Event::Key(event) = event::read()? {
+            let control = event.modifiers.contains(KeyModifiers::CONTROL);
+            match event.code {
+                _ if ui::is_interrupt(&event) => {
+                    input.clear();
+                    break Some(input);
+                }
+                KeyCode::Char('d') if control && input.is_empty() => break None,
+                KeyCode::Char(_) if control => {}
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Up if position > 0 => {
+                    position -= 1;
+                    input = history[position].clone();
+                }
+                KeyCode::Down if position < history.len() => {
+                    position += 1;
+                    input = history.get(position).cloned().unwrap_or_default();
+                }
+                KeyCode::Enter => break Some(input),
+                _ => {}
+            }
+        }
+    };
+    terminal::disable_raw_mode()?;
+    println!();
+    Ok(line)
+}
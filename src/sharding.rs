@@ -0,0 +1,83 @@
+use crate::style::cformat;
+use anyhow::Result;
+use futures::stream::TryStreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    Client,
+};
+
+/// Describes the shards of the cluster, and how `namespace` is distributed over them if it's
+/// given and sharded. Returns `None` unless connected to a mongos.
+pub async fn describe(client: &Client, namespace: Option<&str>) -> Result<Option<Vec<String>>> {
+    if !is_mongos(client).await? {
+        return Ok(None);
+    }
+    let admin = client.database("admin");
+
+    let mut lines = vec![cformat!("<yellow>shards</yellow>")];
+    let shards = admin.run_command(doc! { "listShards": 1 }, None).await?;
+    for shard in shards.get_array("shards").into_iter().flatten() {
+        if let Some(shard) = shard.as_document() {
+            lines.push(cformat!(
+                "  <green>{}</green>  {}",
+                shard.get_str("_id").unwrap_or("?"),
+                shard.get_str("host").unwrap_or("?")
+            ));
+        }
+    }
+
+    if let Some(namespace) = namespace {
+        lines.push(String::new());
+        lines.push(cformat!("<yellow>{}</yellow>", namespace));
+        lines.extend(describe_collection(client, namespace).await?);
+    }
+    Ok(Some(lines))
+}
+
+/// Whether `client` is connected to a sharded cluster through mongos routers.
+pub async fn is_mongos(client: &Client) -> Result<bool> {
+    let admin = client.database("admin");
+    let hello = admin.run_command(doc! { "hello": 1 }, None).await?;
+    Ok(hello.get_str("msg") == Ok("isdbgrid"))
+}
+
+async fn describe_collection(client: &Client, namespace: &str) -> Result<Vec<String>> {
+    let config = client.database("config");
+    let collection = config
+        .collection::<Document>("collections")
+        .find_one(doc! { "_id": namespace, "dropped": { "$ne": true } }, None)
+        .await?;
+    let collection = match collection {
+        Some(collection) => collection,
+        None => return Ok(vec![String::from("  not sharded")]),
+    };
+    let mut lines = vec![cformat!(
+        "  <green>shard key</green>  {}",
+        collection.get_document("key").cloned().unwrap_or_default()
+    )];
+
+    // Chunks refer to their collection by namespace before MongoDB 5.0, by UUID since.
+    let mut owners = vec![doc! { "ns": namespace }];
+    if let Some(uuid) = collection.get("uuid") {
+        owners.push(doc! { "uuid": uuid.clone() });
+    }
+    let pipeline = [
+        doc! { "$match": { "$or": owners } },
+        doc! { "$group": { "_id": "$shard", "chunks": { "$sum": 1 } } },
+        doc! { "$sort": { "_id": 1 } },
+    ];
+    let distribution: Vec<Document> = config
+        .collection::<Document>("chunks")
+        .aggregate(pipeline, None)
+        .await?
+        .try_collect()
+        .await?;
+    for shard in distribution {
+        lines.push(cformat!(
+            "  <green>{}</green>  {} chunks",
+            shard.get_str("_id").unwrap_or("?"),
+            shard.get_i32("chunks").unwrap_or_default()
+        ));
+    }
+    Ok(lines)
+}
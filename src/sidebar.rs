@@ -0,0 +1,153 @@
+use crate::{style::cformat, ui};
+use anyhow::Result;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode},
+    execute,
+    style::Stylize,
+    terminal,
+};
+use mongodb::Client;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+
+/// Columns the sidebar takes at most, including its border.
+const WIDTH: usize = 32;
+
+const KEYS: &str = "enter: open or list the collections | esc: close";
+
+enum Entry<'a> {
+    Database(&'a str),
+    Collection(&'a str, &'a str),
+}
+
+/// Shows the databases over the left of the view, with the collections of the current one
+/// listed under it, until a collection is picked or escape is pressed. Enter lists or hides the
+/// collections of a database. Returns the picked database and collection. Expects the terminal
+/// to be in raw mode and leaves drawing the view again to the caller.
+pub async fn pick(
+    client: &Client,
+    databases: &[String],
+    current: (&str, &str),
+) -> Result<Option<(String, String)>> {
+    let mut expanded: HashMap<String, Vec<String>> = HashMap::new();
+    if let Some(names) = list(client, current.0).await? {
+        expanded.insert(current.0.to_string(), names);
+    }
+    let mut selected = entries(databases, &expanded)
+        .iter()
+        .position(|entry| matches!(entry, Entry::Collection(d, c) if (*d, *c) == current))
+        .unwrap_or_default();
+    let mut top = 0;
+    ui::status(KEYS)?;
+
+    loop {
+        let entries = entries(databases, &expanded);
+        selected = selected.min(entries.len().saturating_sub(1));
+        let (columns, rows) = terminal::size()?;
+        // The status line and the footer stay visible.
+        let height = (rows as usize).saturating_sub(2).max(1);
+        if selected < top {
+            top = selected;
+        } else if selected >= top + height {
+            top = selected + 1 - height;
+        }
+        let width = WIDTH.min(columns as usize / 2).max(2) - 1;
+
+        let mut stdout = io::stdout();
+        for row in 0..height {
+            let text = match entries.get(top + row) {
+                Some(Entry::Database(name)) => {
+                    let marker = match expanded.contains_key(*name) {
+                        true => '▾',
+                        false => '▸',
+                    };
+                    format!("{marker} {name}")
+                }
+                Some(Entry::Collection(_, name)) => format!("    {name}"),
+                None => String::new(),
+            };
+            let text: String = text.chars().take(width).collect();
+            let text = format!("{text:width$}");
+            let text = match entries.get(top + row) {
+                _ if top + row == selected => text.reverse().to_string(),
+                Some(Entry::Database(name)) if *name == current.0 => {
+                    cformat!("<yellow>{}</yellow>", text)
+                }
+                Some(Entry::Collection(database, name)) if (*database, *name) == current => {
+                    cformat!("<green>{}</green>", text)
+                }
+                _ => text,
+            };
+            execute!(stdout, cursor::MoveTo(0, row as u16))?;
+            print!("{text}│");
+        }
+        stdout.flush()?;
+
+        let event = match event::read()? {
+            Event::Key(event) => event,
+            _ => continue,
+        };
+        match event.code {
+            _ if ui::is_interrupt(&event) => return Ok(None),
+            KeyCode::Esc | KeyCode::Tab | KeyCode::Char('q' | 'b') => return Ok(None),
+            KeyCode::Down | KeyCode::Char('j') => selected += 1,
+            KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+            KeyCode::Enter | KeyCode::Char('l') => match entries.get(selected) {
+                Some(Entry::Collection(database, name)) => {
+                    return Ok(Some((database.to_string(), name.to_string())))
+                }
+                Some(Entry::Database(name)) if expanded.contains_key(*name) => {
+                    let name = name.to_string();
+                    expanded.remove(&name);
+                }
+                Some(Entry::Database(name)) => {
+                    let name = name.to_string();
+                    if let Some(names) = list(client, &name).await? {
+                        expanded.insert(name, names);
+                        ui::status(KEYS)?;
+                    }
+                }
+                None => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// The databases, each followed by its collections if they're listed.
+fn entries<'a>(
+    databases: &'a [String],
+    expanded: &'a HashMap<String, Vec<String>>,
+) -> Vec<Entry<'a>> {
+    let mut entries = Vec::new();
+    for database in databases {
+        entries.push(Entry::Database(database));
+        for name in expanded.get(database).into_iter().flatten() {
+            entries.push(Entry::Collection(database, name));
+        }
+    }
+    entries
+}
+
+/// The sorted collection names of `database`, or `None` if listing them was cancelled or
+/// failed, which is reported on the status line.
+async fn list(client: &Client, database: &str) -> Result<Option<Vec<String>>> {
+    let db = client.database(database);
+    match ui::cancellable(db.list_collection_names(None)).await? {
+        Some(Ok(mut names)) => {
+            names.retain(|name| !name.starts_with("system.buckets."));
+            names.sort();
+            Ok(Some(names))
+        }
+        Some(Err(e)) => {
+            ui::status(&format!(
+                "failed to list the collections of {database}: {e}"
+            ))?;
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
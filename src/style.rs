@@ -0,0 +1,56 @@
+//! The `color_print` macros, with their styling left out when colors are disabled with
+//! `--no-color` or `NO_COLOR`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+pub fn disable_color() {
+    COLOR.store(false, Ordering::Relaxed);
+}
+
+pub fn color_enabled() -> bool {
+    COLOR.load(Ordering::Relaxed)
+}
+
+/// `text` as is, or without its SGR escape sequences if colors are disabled.
+pub fn apply(text: String) -> String {
+    if color_enabled() {
+        return text;
+    }
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' && chars.peek() == Some(&'[') {
+            // Parameters and intermediate bytes up to the final `m`.
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+macro_rules! cformat {
+    ($($arg:tt)*) => {
+        $crate::style::apply(color_print::cformat!($($arg)*))
+    };
+}
+
+macro_rules! cprint {
+    ($($arg:tt)*) => {
+        print!("{}", $crate::style::apply(color_print::cformat!($($arg)*)))
+    };
+}
+
+macro_rules! cprintln {
+    ($($arg:tt)*) => {
+        println!("{}", $crate::style::apply(color_print::cformat!($($arg)*)))
+    };
+}
+
+pub(crate) use {cformat, cprint, cprintln};
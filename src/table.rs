@@ -0,0 +1,172 @@
+use crate::render::{self, Dates, Json};
+use mongodb::bson::{doc, spec::BinarySubtype, Bson, Document};
+
+/// Cells are cut off with an ellipsis past this many characters.
+const MAX_CELL_WIDTH: usize = 24;
+
+/// Lines above the first row: the top border, the column names and the separator below them.
+pub const HEADER_LINES: usize = 3;
+
+/// Whether `documents` are flat enough for a table: subdocuments and arrays are shown in a
+/// single cell, so they shouldn't hold subdocuments or arrays of their own.
+pub fn fits(documents: &[Document]) -> bool {
+    !documents
+        .iter()
+        .any(|document| document.values().any(is_nested))
+}
+
+/// Lays `documents` out as a table with a column per field, in the order the fields first
+/// appear, leaving out the `hidden` ones. Returns `None` if they don't `fit` in one.
+pub fn render(
+    documents: &[Document],
+    json: Json,
+    dates: &Dates,
+    hide_id: bool,
+    hidden: &[String],
+) -> Option<Vec<String>> {
+    if !fits(documents) {
+        return None;
+    }
+
+    let columns = columns(documents, hide_id, hidden);
+    let rows: Vec<Vec<String>> = documents
+        .iter()
+        .map(|document| {
+            columns
+                .iter()
+                .map(|column| match document.get(column) {
+                    Some(value) => truncate(text(value, json, dates)),
+                    None => String::new(),
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.chars().count()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let border = |left: &str, middle: &str, right: &str| {
+        let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+        format!("{left}{}{right}", segments.join(middle))
+    };
+    let row = |cells: Vec<&str>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!(" {cell:width$} "))
+            .collect();
+        format!("│{}│", cells.join("│"))
+    };
+
+    let mut lines = vec![
+        border("┌", "┬", "┐"),
+        row(columns.clone()),
+        border("├", "┼", "┤"),
+    ];
+    for cells in &rows {
+        lines.push(row(cells.iter().map(String::as_str).collect()));
+    }
+    lines.push(border("└", "┴", "┘"));
+    Some(lines)
+}
+
+/// Lays `documents` out as a Markdown table, with the same columns as `render` and values in
+/// full. Subdocuments and arrays are written as JSON in a single cell.
+pub fn markdown(
+    documents: &[Document],
+    json: Json,
+    dates: &Dates,
+    hide_id: bool,
+    hidden: &[String],
+) -> String {
+    let columns = columns(documents, hide_id, hidden);
+    let row = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+    let mut lines = vec![
+        row(columns.iter().map(|column| escape(column)).collect()),
+        row(columns.iter().map(|_| String::from("---")).collect()),
+    ];
+    for document in documents {
+        lines.push(row(columns
+            .iter()
+            .map(|column| match document.get(column) {
+                Some(value) => escape(&text(value, json, dates)),
+                None => String::new(),
+            })
+            .collect()));
+    }
+    lines.join("\n")
+}
+
+/// The fields of `documents` other than the `hidden` ones, in the order they first appear.
+pub fn columns<'a>(documents: &'a [Document], hide_id: bool, hidden: &[String]) -> Vec<&'a str> {
+    let mut columns: Vec<&str> = Vec::new();
+    for key in documents.iter().flat_map(|document| document.keys()) {
+        if !(columns.contains(&key.as_str()) || hide_id && key == "_id" || hidden.contains(key)) {
+            columns.push(key);
+        }
+    }
+    columns
+}
+
+/// `text` with the pipes that would end a Markdown cell escaped.
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn is_nested(value: &Bson) -> bool {
+    match value {
+        Bson::Document(document) => document
+            .values()
+            .any(|v| matches!(v, Bson::Document(_) | Bson::Array(_))),
+        Bson::Array(items) => items
+            .iter()
+            .any(|v| matches!(v, Bson::Document(_) | Bson::Array(_))),
+        _ => false,
+    }
+}
+
+/// `value` on a single line, as it's shown in a cell.
+fn text(value: &Bson, json: Json, dates: &Dates) -> String {
+    let text = match value {
+        Bson::String(s) => s.clone(),
+        Bson::DateTime(date) if matches!(json, Json::Relaxed) => dates.show(*date),
+        Bson::Decimal128(n) => format!("NumberDecimal(\"{n}\")"),
+        Bson::Timestamp(ts) => format!("Timestamp({}, {})", ts.time, ts.increment),
+        Bson::Binary(binary)
+            if binary.subtype == BinarySubtype::Uuid && binary.bytes.len() == 16 =>
+        {
+            format!(
+                "UUID(\"{}\")",
+                render::uuid(&binary.bytes).unwrap_or_default()
+            )
+        }
+        Bson::Binary(binary)
+            if binary.subtype == BinarySubtype::UuidOld && binary.bytes.len() == 16 =>
+        {
+            format!(
+                "LUUID(\"{}\")",
+                render::uuid(&binary.bytes).unwrap_or_default()
+            )
+        }
+        value => json.to_value(&doc! { "value": value.clone() })["value"].to_string(),
+    };
+    text.replace(['\n', '\r', '\t'], " ")
+}
+
+/// `text` cut off with an ellipsis past `MAX_CELL_WIDTH` characters.
+fn truncate(text: String) -> String {
+    if text.chars().count() > MAX_CELL_WIDTH {
+        let cut: String = text.chars().take(MAX_CELL_WIDTH - 1).collect();
+        format!("{cut}…")
+    } else {
+        text
+    }
+}
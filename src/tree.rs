@@ -0,0 +1,211 @@
+use crate::{
+    render::{self, Indent},
+    style::cformat,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Arrays with more items than this start out collapsed.
+const LONG_ARRAY: usize = 10;
+
+/// A rendered line of a document.
+pub struct Line {
+    /// Dotted path of the value the line belongs to, e.g. `address.city` or `tags.0`.
+    pub path: String,
+    /// Whether the value is currently collapsed, `None` if it can't be collapsed.
+    pub collapsed: Option<bool>,
+    pub text: String,
+}
+
+/// Renders documents as an indented tree whose subdocuments and arrays can be collapsed, as
+/// can long strings.
+pub struct Tree {
+    /// Collapsed state of the values the user toggled, by path.
+    overrides: HashMap<String, bool>,
+    /// Strings longer than this many characters start out truncated, 0 for none.
+    max_string: usize,
+    /// Subdocuments and arrays nested this deep start out collapsed, 0 for none.
+    max_depth: usize,
+}
+
+impl Tree {
+    pub fn new(max_string: usize, max_depth: usize) -> Self {
+        Tree {
+            overrides: HashMap::new(),
+            max_string,
+            max_depth,
+        }
+    }
+
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn render(&self, document: &Value, indent: Indent) -> Vec<Line> {
+        let mut lines = Vec::new();
+        self.push(&mut lines, indent, String::new(), "", document, 0);
+        lines
+    }
+
+    /// Collapses the value `line` belongs to if it's expanded, and the other way around.
+    pub fn toggle(&mut self, line: &Line) {
+        if let Some(collapsed) = line.collapsed {
+            self.overrides.insert(line.path.clone(), !collapsed);
+        }
+    }
+
+    fn is_collapsed(&self, path: &str, value: &Value, depth: usize) -> bool {
+        let too_deep = self.max_depth > 0 && depth >= self.max_depth;
+        let default = match value {
+            Value::Array(items) => too_deep || items.len() > LONG_ARRAY,
+            Value::Object(_) => too_deep,
+            Value::String(s) => self.max_string > 0 && s.chars().count() > self.max_string,
+            _ => false,
+        };
+        self.overrides.get(path).copied().unwrap_or(default)
+    }
+
+    fn push(
+        &self,
+        lines: &mut Vec<Line>,
+        unit: Indent,
+        label: String,
+        path: &str,
+        value: &Value,
+        depth: usize,
+    ) {
+        let indent = unit.repeat(depth);
+        let children: Vec<(String, String, &Value)> = match value {
+            // Shown the way the shell does, rather than as a document of its own.
+            Value::Object(fields) if shell_scalar(fields).is_some() => {
+                lines.push(Line {
+                    path: path.to_string(),
+                    collapsed: None,
+                    text: format!(
+                        "{indent}{label}{}",
+                        shell_scalar(fields).unwrap_or_default()
+                    ),
+                });
+                return;
+            }
+            Value::Object(fields) => fields
+                .iter()
+                .map(|(key, value)| (cformat!("<green>{}</green>: ", key), key.clone(), value))
+                .collect(),
+            Value::Array(items) => items
+                .iter()
+                .enumerate()
+                .map(|(i, value)| (cformat!("<cyan>[{}]</cyan> ", i), i.to_string(), value))
+                .collect(),
+            Value::String(s) if self.max_string > 0 && s.chars().count() > self.max_string => {
+                let collapsed = self.is_collapsed(path, value, depth);
+                let text = match collapsed {
+                    true => {
+                        let cut: String = s.chars().take(self.max_string).collect();
+                        let cut = Value::String(cut).to_string();
+                        let length = s.chars().count();
+                        cformat!(
+                            "{}<dim>… ({} chars)</dim>",
+                            &cut[..cut.len() - 1],
+                            thousands(length)
+                        )
+                    }
+                    false => value.to_string(),
+                };
+                lines.push(Line {
+                    path: path.to_string(),
+                    collapsed: Some(collapsed),
+                    text: format!("{indent}{label}{text}"),
+                });
+                return;
+            }
+            scalar => {
+                lines.push(Line {
+                    path: path.to_string(),
+                    collapsed: None,
+                    text: format!("{indent}{label}{scalar}"),
+                });
+                return;
+            }
+        };
+
+        let (open, close, noun) = match value {
+            Value::Array(_) => ('[', ']', "items"),
+            _ => ('{', '}', "fields"),
+        };
+        if children.is_empty() {
+            lines.push(Line {
+                path: path.to_string(),
+                collapsed: None,
+                text: format!("{indent}{label}{open}{close}"),
+            });
+            return;
+        }
+        if self.is_collapsed(path, value, depth) {
+            lines.push(Line {
+                path: path.to_string(),
+                collapsed: Some(true),
+                text: format!("{indent}{label}{open}… {} {noun}{close}", children.len()),
+            });
+            return;
+        }
+
+        lines.push(Line {
+            path: path.to_string(),
+            collapsed: Some(false),
+            text: format!("{indent}{label}{open}"),
+        });
+        for (child_label, key, child) in children {
+            let child_path = match path {
+                "" => key,
+                _ => format!("{path}.{key}"),
+            };
+            self.push(lines, unit, child_label, &child_path, child, depth + 1);
+        }
+        lines.push(Line {
+            path: path.to_string(),
+            collapsed: Some(false),
+            text: format!("{indent}{close}"),
+        });
+    }
+}
+
+/// `n` with commas between thousands, e.g. `1,234`.
+fn thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// The shell's notation for a decimal, `{"$numberDecimal": "…"}`, a relaxed date,
+/// `{"$date": "…"}`, a UUID, `{"$uuid": "…"}` or a subtype 3 or 4 `$binary`, or a timestamp,
+/// `{"$timestamp": {"t": …, "i": …}}`, in extended JSON.
+fn shell_scalar(fields: &serde_json::Map<String, Value>) -> Option<String> {
+    let (key, value) = fields.iter().next().filter(|_| fields.len() == 1)?;
+    if key == "$binary" {
+        let name = match value["subType"].as_str()? {
+            "03" => "LUUID",
+            "04" => "UUID",
+            _ => return None,
+        };
+        let bytes = STANDARD.decode(value["base64"].as_str()?).ok()?;
+        return Some(format!("{name}(\"{}\")", render::uuid(&bytes)?));
+    }
+    if key == "$timestamp" {
+        return Some(format!("Timestamp({}, {})", value["t"], value["i"]));
+    }
+    let value = value.as_str()?;
+    match key.as_str() {
+        "$numberDecimal" => Some(format!("NumberDecimal(\"{value}\")")),
+        "$date" => Some(format!("Date(\"{value}\")")),
+        "$uuid" => Some(format!("UUID(\"{value}\")")),
+        _ => None,
+    }
+}
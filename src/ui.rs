@@ -0,0 +1,625 @@
+use crate::{editor, style};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    style::Stylize,
+    terminal::{self, ClearType},
+};
+use std::{
+    collections::hash_map::RandomState,
+    env,
+    fs::{self, OpenOptions},
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    io::{self, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// Frames of the spinner shown while waiting for the server.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Completions listed after the hint of `prompt_with_completion`.
+const SHOWN_COMPLETIONS: usize = 8;
+
+/// Prints a message on the line above the footer without moving the cursor.
+pub fn status(message: &str) -> Result<()> {
+    let (_, rows) = terminal::size()?;
+    draw_line(rows.saturating_sub(2), message)
+}
+
+/// Prints `text` on the last line of the terminal without moving the cursor.
+pub fn footer(text: &str) -> Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let text = format!("{text:width$}", width = columns as usize);
+    draw_line(rows.saturating_sub(1), &format!("{}", text.reverse()))
+}
+
+/// Like `footer`, starting with `banner` in white on red, to stand out.
+pub fn footer_with_banner(banner: &str, text: &str) -> Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let width = (columns as usize).saturating_sub(banner.chars().count());
+    let text = format!("{text:width$}");
+    let banner = match style::color_enabled() {
+        true => format!("{}", banner.white().on_red().bold()),
+        false => banner.to_string(),
+    };
+    draw_line(
+        rows.saturating_sub(1),
+        &format!("{banner}{}", text.reverse()),
+    )
+}
+
+fn draw_line(row: u16, text: &str) -> Result<()> {
+    execute!(
+        io::stdout(),
+        cursor::SavePosition,
+        cursor::MoveTo(0, row),
+        terminal::Clear(ClearType::CurrentLine),
+    )?;
+    print!("{text}");
+    execute!(io::stdout(), cursor::RestorePosition)?;
+    Ok(())
+}
+
+/// Shows `text` wrapped over the two lines just above the status line,
+/// cutting it off with an ellipsis if it doesn't fit.
+pub fn preview(text: &str) -> Result<()> {
+    let (columns, rows) = terminal::size()?;
+    let width = columns.max(1) as usize;
+    let chars: Vec<char> = text.chars().collect();
+    let mut lines: Vec<String> = chars.chunks(width).take(2).map(String::from_iter).collect();
+    if chars.len() > width * 2 {
+        if let Some(last) = lines.last_mut() {
+            last.pop();
+            last.push('…');
+        }
+    }
+
+    let mut stdout = io::stdout();
+    execute!(stdout, cursor::SavePosition)?;
+    for i in 0..2 {
+        execute!(
+            stdout,
+            cursor::MoveTo(0, rows.saturating_sub(4) + i as u16),
+            terminal::Clear(ClearType::CurrentLine),
+        )?;
+        if let Some(line) = lines.get(i) {
+            print!("{line}");
+        }
+    }
+    execute!(stdout, cursor::RestorePosition)?;
+    Ok(())
+}
+
+/// Reads a line of input on the status line.
+/// Returns `None` if the prompt was cancelled with escape.
+pub fn prompt(label: &str) -> Result<Option<String>> {
+    prompt_with_hint(label, |_| String::new())
+}
+
+/// Like `prompt`, showing what `hint` makes of the input so far after it, e.g. whether the
+/// braces of a document are balanced.
+pub fn prompt_with_hint(label: &str, hint: impl Fn(&str) -> String) -> Result<Option<String>> {
+    prompt_with_completion(label, hint, &[])
+}
+
+/// Like `prompt_with_hint`, completing the word being typed from `words` on tab, and listing
+/// the words it could become after the hint.
+pub fn prompt_with_completion(
+    label: &str,
+    hint: impl Fn(&str) -> String,
+    words: &[String],
+) -> Result<Option<String>> {
+    read_line(label, "", &hint, words, None)
+}
+
+/// Like `prompt`, starting with `input` already typed.
+pub fn prompt_with_input(label: &str, input: &str) -> Result<Option<String>> {
+    read_line(label, input, &|_| String::new(), &[], None)
+}
+
+/// Like `prompt_with_completion`, for JSON: ctrl-e moves the input to a multi-line editor,
+/// which checks it with `validate` as it's edited.
+pub fn prompt_json(
+    label: &str,
+    hint: impl Fn(&str) -> String,
+    words: &[String],
+    validate: impl Fn(&str) -> Result<(), String>,
+) -> Result<Option<String>> {
+    read_line(label, "", &hint, words, Some(&validate))
+}
+
+fn read_line(
+    label: &str,
+    input: &str,
+    hint: &dyn Fn(&str) -> String,
+    words: &[String],
+    validate: Option<&editor::Validate>,
+) -> Result<Option<String>> {
+    let mut stdout = io::stdout();
+    let (_, rows) = terminal::size()?;
+    let mut input = input.to_string();
+    execute!(stdout, cursor::SavePosition)?;
+
+    let result = loop {
+        execute!(
+            stdout,
+            cursor::MoveTo(0, rows.saturating_sub(2)),
+            terminal::Clear(ClearType::CurrentLine),
+        )?;
+        print!("{label}{input}");
+        let mut hint = hint(&input);
+        if validate.is_some() {
+            hint = format!("{hint}  ctrl-e: multi-line editor")
+                .trim_start()
+                .to_string();
+        }
+        let candidates = completions(&input, words);
+        if !candidates.is_empty() {
+            let mut shown = candidates[..candidates.len().min(SHOWN_COMPLETIONS)].join(" ");
+            if candidates.len() > SHOWN_COMPLETIONS {
+                shown.push_str(" …");
+            }
+            hint = format!("{hint}  {shown}");
+        }
+        if !hint.is_empty() {
+            let column = label.chars().count() + input.chars().count();
+            match style::color_enabled() {
+                true => print!("  {}", hint.dim()),
+                false => print!("  {hint}"),
+            }
+            execute!(stdout, cursor::MoveToColumn(column as u16))?;
+        }
+        stdout.flush()?;
+
+        if let Event::Key(event) = event::read()? {
+            match event.code {
+                _ if is_interrupt(&event) => break None,
+                KeyCode::Enter => break Some(input),
+                KeyCode::Esc => break None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char('e') if event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    if let Some(validate) = validate {
+                        let title = label.trim().trim_end_matches(':');
+                        if let Some(text) = editor::edit(title, &input, validate)? {
+                            break Some(text);
+                        }
+                    }
+                }
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Tab => {
+                    // Completes up to where the candidates differ.
+                    let word = current_word(&input);
+                    let completed = candidates.iter().fold(None, |common: Option<&str>, c| {
+                        Some(match common {
+                            None => c,
+                            Some(common) => {
+                                let shared = common
+                                    .char_indices()
+                                    .zip(c.chars())
+                                    .find(|((_, a), b)| a != b)
+                                    .map_or(common.len().min(c.len()), |((i, _), _)| i);
+                                &common[..shared]
+                            }
+                        })
+                    });
+                    if let Some(completed) = completed {
+                        input.push_str(&completed[word.len()..]);
+                    }
+                }
+                _ => {}
+            }
+        }
+    };
+
+    execute!(
+        stdout,
+        terminal::Clear(ClearType::CurrentLine),
+        cursor::RestorePosition
+    )?;
+    Ok(result)
+}
+
+/// The trailing word of `input`, e.g. `addr` in `{name: 'x', addr`.
+fn current_word(input: &str) -> &str {
+    let start = input
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '$')))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    &input[start..]
+}
+
+/// The words the trailing word of `input` could be completed to.
+fn completions<'a>(input: &str, words: &'a [String]) -> Vec<&'a str> {
+    let word = current_word(input);
+    if word.is_empty() {
+        return Vec::new();
+    }
+    words
+        .iter()
+        .map(String::as_str)
+        .filter(|candidate| candidate.starts_with(word) && *candidate != word)
+        .collect()
+}
+
+/// `text` cut off to fit on a line of `width` columns after `prefix`.
+pub fn single_line(prefix: &str, text: &str, width: usize) -> String {
+    text.chars()
+        .take(width.saturating_sub(prefix.chars().count()))
+        .collect()
+}
+
+/// Asks a yes/no question on the status line.
+pub fn confirm(question: &str) -> Result<bool> {
+    confirm_within(question, None)
+}
+
+/// Like `confirm`, answering no if nothing was pressed within `timeout`.
+pub fn confirm_within(question: &str, timeout: Option<Duration>) -> Result<bool> {
+    let deadline = timeout.map(|timeout| (Instant::now() + timeout, timeout.as_secs()));
+    match deadline {
+        Some((_, seconds)) => status(&format!("{question} (y/n, cancels in {seconds}s)"))?,
+        None => status(&format!("{question} (y/n)"))?,
+    }
+    let answer = loop {
+        if let Some((deadline, _)) = deadline {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                status("cancelled")?;
+                return Ok(false);
+            }
+            if !event::poll(left)? {
+                continue;
+            }
+        }
+        if let Event::Key(event) = event::read()? {
+            match event.code {
+                _ if is_interrupt(&event) => break false,
+                KeyCode::Char('y') => break true,
+                KeyCode::Char('n') | KeyCode::Esc => break false,
+                _ => {}
+            }
+        }
+    };
+    status("")?;
+    Ok(answer)
+}
+
+/// Saves the terminal's window title, for `restore_title`. Terminals without a title stack
+/// ignore this.
+pub fn save_title() -> Result<()> {
+    print!("\x1b[22;0t");
+    io::stdout().flush()?;
+    Ok(())
+}
+
+pub fn restore_title() -> Result<()> {
+    print!("\x1b[23;0t");
+    io::stdout().flush()?;
+    Ok(())
+}
+
+pub fn set_title(title: &str) -> Result<()> {
+    execute!(io::stdout(), terminal::SetTitle(title))?;
+    Ok(())
+}
+
+/// Puts `text` on the clipboard through the terminal with the OSC 52 escape sequence,
+/// which also works over SSH but isn't supported by every terminal.
+pub fn copy(text: &str) -> Result<()> {
+    print!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    io::stdout().flush()?;
+    Ok(())
+}
+
+/// Opens `url` in the default browser, the way the desktop opens files.
+pub fn open(url: &str) -> Result<()> {
+    let (program, args): (&str, &[&str]) = if cfg!(target_os = "macos") {
+        ("open", &[])
+    } else if cfg!(windows) {
+        // The empty title keeps start from taking the quoted URL for one.
+        ("cmd", &["/C", "start", ""])
+    } else {
+        ("xdg-open", &[])
+    };
+    let status = Command::new(program)
+        .args(args)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(anyhow!("{program} exited with {status}")),
+        Err(e) => Err(anyhow!("failed to run {program}: {e}")),
+    }
+}
+
+/// Opens `text` in `$VISUAL` or `$EDITOR` and returns it as saved. If neither is set, edits it
+/// in the built-in editor under `title` instead, checking it with `validate`, and returns
+/// `None` if that's cancelled.
+pub fn edit(text: &str, title: &str, validate: &editor::Validate) -> Result<Option<String>> {
+    let editor = match env::var("VISUAL").or_else(|_| env::var("EDITOR")) {
+        Ok(editor) if !editor.trim().is_empty() => editor,
+        _ => return editor::edit(title, text, validate),
+    };
+    let path = create_temp_file(text).context("failed to write the file to edit")?;
+    // Editors are often given with arguments, e.g. `code --wait`.
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+
+    terminal::disable_raw_mode()?;
+    let status = Command::new(program).args(words).arg(&path).status();
+    terminal::enable_raw_mode()?;
+
+    let edited = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path)
+            .map(Some)
+            .map_err(anyhow::Error::from),
+        Ok(status) => Err(anyhow!("{program} exited with {status}")),
+        Err(e) => Err(anyhow!("failed to run {program}: {e}")),
+    };
+    let _ = fs::remove_file(&path);
+    edited
+}
+
+/// Writes `text` to a new file in the temporary directory only the user can read, under a
+/// random name. The file is never one that already exists, such as a link another user put
+/// there.
+fn create_temp_file(text: &str) -> io::Result<PathBuf> {
+    loop {
+        // Hashers are seeded with random keys.
+        let random = RandomState::new().build_hasher().finish();
+        let path = env::temp_dir().join(format!("termongo-{random:016x}.json"));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(text.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Shows `lines` over the whole screen, scrolled with up/down or j/k and page up/down,
+/// until escape or q is pressed. Returns the key if it's one of `actions` instead.
+/// Expects the terminal to be in raw mode.
+pub fn panel(lines: &[String], actions: &[char]) -> Result<Option<char>> {
+    let mut stdout = io::stdout();
+    // The cursor row is the selection of the view underneath, so it's put back on close.
+    let selected = cursor::position()?.1;
+    let mut top = 0;
+    let choice = loop {
+        let (_, rows) = terminal::size()?;
+        // The last row is left for the position and the keys.
+        let height = (rows as usize).saturating_sub(1).max(1);
+        let last_top = lines.len().saturating_sub(height);
+        top = top.min(last_top);
+
+        execute!(
+            stdout,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All)
+        )?;
+        for (row, line) in lines.iter().skip(top).take(height).enumerate() {
+            execute!(stdout, cursor::MoveTo(0, row as u16))?;
+            print!("{line}");
+        }
+        footer(&format!(
+            " {}-{} of {} | ↑/↓ pgup/pgdn: scroll | esc: close",
+            (top + 1).min(lines.len()),
+            (top + height).min(lines.len()),
+            lines.len()
+        ))?;
+        stdout.flush()?;
+
+        if let Event::Key(event) = event::read()? {
+            match event.code {
+                KeyCode::Up | KeyCode::Char('k') => top = top.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => top += 1,
+                KeyCode::PageUp => top = top.saturating_sub(height),
+                KeyCode::PageDown => top += height,
+                KeyCode::Home => top = 0,
+                KeyCode::End => top = last_top,
+                _ if is_interrupt(&event) => break None,
+                KeyCode::Esc | KeyCode::Char('q') => break None,
+                KeyCode::Char(c) if actions.contains(&c) => break Some(c),
+                _ => {}
+            }
+        }
+    };
+    execute!(stdout, cursor::MoveTo(0, selected))?;
+    Ok(choice)
+}
+
+/// Shows `title` over `items`, one per line, for picking one with up/down or j/k and enter.
+/// Returns the index of the picked item, or `None` if escape or q was pressed.
+/// Expects the terminal to be in raw mode.
+pub fn choose(title: &str, items: &[String]) -> Result<Option<usize>> {
+    let mut stdout = io::stdout();
+    let mut selected = 0;
+    let mut top = 0;
+    loop {
+        let (_, rows) = terminal::size()?;
+        // The first row is the title and the last one the keys.
+        let height = (rows as usize).saturating_sub(2).max(1);
+        if selected < top {
+            top = selected;
+        } else if selected >= top + height {
+            top = selected + 1 - height;
+        }
+
+        execute!(
+            stdout,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All)
+        )?;
+        print!("{title}");
+        for (row, item) in items.iter().enumerate().skip(top).take(height) {
+            execute!(stdout, cursor::MoveTo(0, (row - top + 1) as u16))?;
+            match row == selected {
+                true => print!("{}", item.as_str().reverse()),
+                false => print!("{item}"),
+            }
+        }
+        footer(&format!(
+            " {}/{} | ↑/↓: move | enter: open | esc: close",
+            (selected + 1).min(items.len()),
+            items.len()
+        ))?;
+        stdout.flush()?;
+
+        if let Event::Key(event) = event::read()? {
+            match event.code {
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') if selected + 1 < items.len() => selected += 1,
+                KeyCode::PageUp => selected = selected.saturating_sub(height),
+                KeyCode::PageDown => selected = (selected + height).min(items.len().max(1) - 1),
+                KeyCode::Enter if !items.is_empty() => return Ok(Some(selected)),
+                _ if is_interrupt(&event) => return Ok(None),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Like `choose`, for turning any of `items` on or off with space, starting from `on`.
+/// Returns whether enter was pressed to keep the changes, rather than escape or q.
+/// Expects the terminal to be in raw mode.
+pub fn toggle(title: &str, items: &[String], on: &mut [bool]) -> Result<bool> {
+    let mut stdout = io::stdout();
+    let mut selected = 0;
+    let mut top = 0;
+    loop {
+        let (_, rows) = terminal::size()?;
+        // The first row is the title and the last one the keys.
+        let height = (rows as usize).saturating_sub(2).max(1);
+        if selected < top {
+            top = selected;
+        } else if selected >= top + height {
+            top = selected + 1 - height;
+        }
+
+        execute!(
+            stdout,
+            cursor::MoveTo(0, 0),
+            terminal::Clear(ClearType::All)
+        )?;
+        print!("{title}");
+        for (row, item) in items.iter().enumerate().skip(top).take(height) {
+            execute!(stdout, cursor::MoveTo(0, (row - top + 1) as u16))?;
+            let item = match on[row] {
+                true => format!("[x] {item}"),
+                false => format!("[ ] {item}"),
+            };
+            match row == selected {
+                true => print!("{}", item.as_str().reverse()),
+                false => print!("{item}"),
+            }
+        }
+        footer(&format!(
+            " {}/{} | ↑/↓: move | space: toggle | a: all | enter: keep | esc: cancel",
+            (selected + 1).min(items.len()),
+            items.len()
+        ))?;
+        stdout.flush()?;
+
+        if let Event::Key(event) = event::read()? {
+            match event.code {
+                KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') if selected + 1 < items.len() => selected += 1,
+                KeyCode::PageUp => selected = selected.saturating_sub(height),
+                KeyCode::PageDown => selected = (selected + height).min(items.len().max(1) - 1),
+                KeyCode::Char(' ') if !items.is_empty() => on[selected] = !on[selected],
+                KeyCode::Char('a') => {
+                    let all = !on.iter().all(|&on| on);
+                    on.iter_mut().for_each(|on| *on = all);
+                }
+                KeyCode::Enter => return Ok(true),
+                _ if is_interrupt(&event) => return Ok(false),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Runs `query` while watching for escape, dropping the query if it's pressed.
+/// Returns `None` if the query was cancelled.
+pub async fn cancellable<T>(query: impl Future<Output = T>) -> Result<Option<T>> {
+    cancellable_as("loading…", query).await
+}
+
+/// Like `cancellable`, showing `message` while `query` runs, after a spinner and, once it's
+/// taken a while, the time it's been running for.
+pub async fn cancellable_as<T>(message: &str, query: impl Future<Output = T>) -> Result<Option<T>> {
+    let started = Instant::now();
+    let mut ticks = tokio::time::interval(SPINNER_INTERVAL);
+    tokio::pin!(query);
+    let escape = wait_for_escape();
+    tokio::pin!(escape);
+    let result = loop {
+        tokio::select! {
+            result = &mut query => break Some(result),
+            escaped = &mut escape => {
+                escaped?;
+                break None;
+            }
+            _ = ticks.tick() => {
+                let elapsed = started.elapsed();
+                let frame = elapsed.as_millis() / SPINNER_INTERVAL.as_millis();
+                let frame = SPINNER[frame as usize % SPINNER.len()];
+                let elapsed = match elapsed.as_secs() {
+                    0 => String::new(),
+                    _ => format!(" {:.1}s", elapsed.as_secs_f64()),
+                };
+                status(&format!("{frame} {message}{elapsed} (esc or ctrl-c to cancel)"))?;
+            }
+        }
+    };
+    status("")?;
+    Ok(result)
+}
+
+async fn wait_for_escape() -> Result<()> {
+    loop {
+        let key = next_key().await?;
+        if key.code == KeyCode::Esc || is_interrupt(&key) {
+            return Ok(());
+        }
+    }
+}
+
+/// Whether `key` is ctrl-c, which raw mode delivers as a key press rather than a signal.
+pub fn is_interrupt(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+/// Waits for a key press without blocking the runtime, so it can be raced against queries.
+pub async fn next_key() -> Result<KeyEvent> {
+    loop {
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                return Ok(key);
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{
+    errors::describe_unauthorized,
+    style::{cformat, cprint},
+    ui,
+};
+use anyhow::{anyhow, Result};
+use crossterm::{
+    cursor,
+    event::KeyCode,
+    terminal::{self, ClearType},
+};
+use futures::stream::StreamExt;
+use mongodb::{
+    bson::Document,
+    change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken},
+    error::{Error, ErrorKind},
+    options::ChangeStreamOptions,
+    Collection,
+};
+
+/// Prints the changes made to `collection` as they happen, until q or escape is pressed.
+/// Starts after `token`, if there is one, and keeps it up to date with the last change seen,
+/// so the caller can save it to resume from, even when watching fails. Expects the terminal to
+/// be in raw mode.
+pub async fn watch(
+    collection: &Collection<Document>,
+    token: &mut Option<ResumeToken>,
+) -> Result<()> {
+    print!(
+        "{}{}",
+        cursor::MoveTo(0, 0),
+        terminal::Clear(ClearType::All),
+    );
+    cprint!(
+        "<yellow>{}</yellow> <cyan>watching, q to stop</cyan>\r\n",
+        collection.namespace()
+    );
+
+    let options = ChangeStreamOptions::builder()
+        .resume_after(token.clone())
+        .build();
+    let mut stream = match collection.watch(None, options).await {
+        Err(e) if token.is_some() && is_unresumable(&e) => {
+            cprint!(
+                "<magenta>can't resume where the last watch stopped ({}), \
+                 watching from now on</magenta>\r\n",
+                e.kind
+            );
+            *token = None;
+            collection.watch(None, None).await.map_err(describe)?
+        }
+        stream => {
+            if token.is_some() {
+                cprint!("<magenta>resumed where the last watch stopped</magenta>\r\n");
+            }
+            stream.map_err(describe)?
+        }
+    };
+
+    let (columns, _) = terminal::size()?;
+    loop {
+        tokio::select! {
+            event = stream.next() => {
+                let event = match event {
+                    Some(event) => event.map_err(describe)?,
+                    None => return Err(anyhow!("the change stream was closed")),
+                };
+                // Nothing can be resumed after an invalidate, e.g. once the collection is dropped.
+                if event.operation_type == OperationType::Invalidate {
+                    *token = None;
+                    return Err(anyhow!("the change stream was invalidated"));
+                }
+                *token = stream.resume_token();
+                print!("{}\r\n", describe_event(&event, columns as usize));
+            }
+            key = ui::next_key() => {
+                let key = key?;
+                if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) || ui::is_interrupt(&key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Whether resuming failed because the token is invalid or its events are gone from the oplog.
+fn is_unresumable(error: &Error) -> bool {
+    // InvalidResumeToken, ChangeStreamFatalError and ChangeStreamHistoryLost.
+    matches!(*error.kind, ErrorKind::Command(ref command) if [260, 280, 286].contains(&command.code))
+}
+
+fn describe(error: Error) -> anyhow::Error {
+    match *error.kind {
+        // The $changeStream stage is only supported on replica sets.
+        ErrorKind::Command(ref command) if command.code == 40573 => {
+            anyhow!("change streams need a replica set or sharded cluster")
+        }
+        _ => describe_unauthorized(error, "watch the collection"),
+    }
+}
+
+/// Renders a change on a single line: cluster time, operation, `_id` and what changed.
+fn describe_event(event: &ChangeStreamEvent<Document>, width: usize) -> String {
+    let time = match event.cluster_time {
+        Some(ts) => format!("{}.{}", ts.time, ts.increment),
+        None => String::from("?"),
+    };
+    let op = match &event.operation_type {
+        OperationType::Insert => "insert",
+        OperationType::Update => "update",
+        OperationType::Replace => "replace",
+        OperationType::Delete => "delete",
+        OperationType::Drop => "drop",
+        OperationType::Rename => "rename",
+        OperationType::DropDatabase => "dropDatabase",
+        OperationType::Invalidate => "invalidate",
+        OperationType::Other(other) => other,
+        _ => "?",
+    };
+    let id = event
+        .document_key
+        .as_ref()
+        .and_then(|key| key.get("_id"))
+        .map(|id| id.to_string())
+        .unwrap_or_default();
+    let change = match (&event.update_description, &event.full_document) {
+        (Some(update), _) if update.removed_fields.is_empty() => {
+            format!("set {}", update.updated_fields)
+        }
+        (Some(update), _) => format!(
+            "set {} unset {}",
+            update.updated_fields,
+            update.removed_fields.join(", ")
+        ),
+        (None, Some(document)) => document.to_string(),
+        (None, None) => String::new(),
+    };
+
+    let prefix = format!("{time} {op:7} {id} ");
+    let change = ui::single_line(&prefix, &change, width);
+    cformat!(
+        "<dim>{}</dim> <green>{:7}</green> <yellow>{}</yellow> {}",
+        time,
+        op,
+        id,
+        change
+    )
+}